@@ -0,0 +1,159 @@
+use serde_json;
+use serde_json::Value as Json;
+use std::time::Duration;
+use Element;
+
+/// A sequence of low-level input [actions] to perform using `Client::perform_actions`.
+///
+/// Steps are performed one after the other, in the order they were added to the chain. Under the
+/// hood, each step is performed in its own "tick", during which all the other input sources
+/// pause.
+///
+/// [actions]: https://www.w3.org/TR/webdriver/#actions
+#[derive(Clone, Debug, Default)]
+pub struct ActionChain {
+    pointer: Vec<Json>,
+    key: Vec<Json>,
+    /// Pointer moves relative to an element's top-left corner, as `(step, dx, dy)`.
+    ///
+    /// The spec only has moves relative to an element's center, so these are recorded as such,
+    /// and only fixed up once the element's size is known.
+    corner_offsets: Vec<(usize, f64, f64)>,
+}
+
+fn pause() -> Json {
+    serde_json::json!({ "type": "pause" })
+}
+
+impl ActionChain {
+    /// Start building a new, empty chain of actions.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    fn pointer_step(mut self, action: Json) -> Self {
+        self.pointer.push(action);
+        self.key.push(pause());
+        self
+    }
+
+    fn key_step(mut self, action: Json) -> Self {
+        self.key.push(action);
+        self.pointer.push(pause());
+        self
+    }
+
+    /// Move the mouse to the center of the given element.
+    pub fn move_to_element(self, element: &Element) -> Self {
+        self.move_to_element_center_offset(element, 0, 0)
+    }
+
+    /// Move the mouse to the point `(dx, dy)` pixels away from the top-left corner of the given
+    /// element.
+    ///
+    /// Since the mouse can only be moved by whole pixels, the point is rounded to the nearest
+    /// pixel. The element's size is looked up when the chain is performed.
+    pub fn move_to_element_with_offset(self, element: &Element, dx: f64, dy: f64) -> Self {
+        let step = self.pointer.len();
+        let mut chain = self.move_to_element(element);
+        chain.corner_offsets.push((step, dx, dy));
+        chain
+    }
+
+    /// Move the mouse to the point `(x, y)` pixels away from the center of the given element.
+    pub(crate) fn move_to_element_center_offset(self, element: &Element, x: i64, y: i64) -> Self {
+        let origin: Json = via_json!(&element.web_element());
+        self.pointer_step(serde_json::json!({
+            "type": "pointerMove",
+            "duration": 0,
+            "origin": origin,
+            "x": x,
+            "y": y,
+        }))
+    }
+
+    /// Press and release the left mouse button at the mouse's current position.
+    pub fn click(self) -> Self {
+        self.pointer_step(serde_json::json!({ "type": "pointerDown", "button": 0 }))
+            .pointer_step(serde_json::json!({ "type": "pointerUp", "button": 0 }))
+    }
+
+    /// Press down the given key.
+    ///
+    /// Special keys, such as Control or Backspace, are given by the code points listed in the
+    /// [keyboard actions] section of the specification (e.g., `'\u{E009}'` for Control).
+    ///
+    /// [keyboard actions]: https://www.w3.org/TR/webdriver/#keyboard-actions
+    pub fn key_down(self, key: char) -> Self {
+        self.key_step(serde_json::json!({ "type": "keyDown", "value": key.to_string() }))
+    }
+
+    /// Release the given key.
+    ///
+    /// See `key_down` for how to specify special keys.
+    pub fn key_up(self, key: char) -> Self {
+        self.key_step(serde_json::json!({ "type": "keyUp", "value": key.to_string() }))
+    }
+
+    /// Type the given text by pressing and releasing the key for each character in turn.
+    pub fn send_keys(self, text: &str) -> Self {
+        text.chars()
+            .fold(self, |chain, c| chain.key_down(c).key_up(c))
+    }
+
+    /// Wait for the given duration before moving on to the next step.
+    ///
+    /// This is useful when the page reacts to input with a delay, such as with animations or
+    /// debounced event handlers.
+    pub fn pause(mut self, duration: Duration) -> Self {
+        let ms = duration.as_secs() * 1000 + u64::from(duration.subsec_millis());
+        let action = serde_json::json!({ "type": "pause", "duration": ms });
+        self.pointer.push(action.clone());
+        self.key.push(action);
+        self
+    }
+
+    /// The elements that pointer moves relative to an element's top-left corner are relative to,
+    /// in the order they were added to the chain.
+    pub(crate) fn corner_origins(&self) -> Vec<Json> {
+        self.corner_offsets
+            .iter()
+            .map(|&(step, _, _)| self.pointer[step]["origin"].clone())
+            .collect()
+    }
+
+    /// Fix up pointer moves relative to an element's top-left corner, given the `(width, height)`
+    /// of each of the elements returned by `corner_origins`.
+    pub(crate) fn resolve_corner_offsets(&mut self, sizes: &[(f64, f64)]) {
+        for (&(step, dx, dy), &(width, height)) in self.corner_offsets.iter().zip(sizes) {
+            self.pointer[step]["x"] = Json::from((dx - width / 2.0).round() as i64);
+            self.pointer[step]["y"] = Json::from((dy - height / 2.0).round() as i64);
+        }
+        self.corner_offsets.clear();
+    }
+
+    /// The actions, as expected by the [Perform Actions] command.
+    ///
+    /// Input sources that only ever pause are left out.
+    ///
+    /// [Perform Actions]: https://www.w3.org/TR/webdriver/#perform-actions
+    pub(crate) fn into_json(self) -> Json {
+        let mut sources = Vec::new();
+        if self.pointer.iter().any(|a| a["type"] != "pause") {
+            sources.push(serde_json::json!({
+                "type": "pointer",
+                "id": "mouse",
+                "parameters": { "pointerType": "mouse" },
+                "actions": self.pointer,
+            }));
+        }
+        if self.key.iter().any(|a| a["type"] != "pause") {
+            sources.push(serde_json::json!({
+                "type": "key",
+                "id": "keyboard",
+                "actions": self.key,
+            }));
+        }
+        serde_json::json!({ "actions": sources })
+    }
+}
@@ -18,7 +18,23 @@ pub enum NewSessionError {
     /// The server did not give a WebDriver-conforming response.
     NotW3C(serde_json::Value),
     /// The WebDriver server refused to create a new session.
-    SessionNotCreated(wderror::WebDriverError),
+    ///
+    /// This carries the error the driver reported while negotiating capabilities, which usually
+    /// tells you exactly which capability was rejected.
+    SessionNotCreated {
+        /// The WebDriver [error code] returned by the server.
+        ///
+        /// [error code]: https://www.w3.org/TR/webdriver/#dfn-error-code
+        error: wderror::ErrorStatus,
+        /// The human-readable message the server gave for the failure.
+        message: String,
+        /// The stack trace reported by the server, if any (empty otherwise).
+        stacktrace: String,
+    },
+    /// The WebDriver server rejected the request for a new session with some other error.
+    ///
+    /// This is usually `invalid argument`, which drivers return for malformed capabilities.
+    Standard(WebDriverError),
 }
 
 impl Error for NewSessionError {
@@ -28,7 +44,8 @@ impl Error for NewSessionError {
             NewSessionError::Failed(..) => "webdriver server did not respond",
             NewSessionError::Lost(..) => "webdriver server disconnected",
             NewSessionError::NotW3C(..) => "webdriver server gave non-conformant response",
-            NewSessionError::SessionNotCreated(..) => "webdriver did not create session",
+            NewSessionError::SessionNotCreated { .. } => "webdriver did not create session",
+            NewSessionError::Standard(..) => "webdriver rejected session request",
         }
     }
 
@@ -37,8 +54,8 @@ impl Error for NewSessionError {
             NewSessionError::BadWebdriverUrl(ref e) => Some(e),
             NewSessionError::Failed(ref e) => Some(e),
             NewSessionError::Lost(ref e) => Some(e),
-            NewSessionError::NotW3C(..) => None,
-            NewSessionError::SessionNotCreated(ref e) => Some(e),
+            NewSessionError::Standard(ref e) => Some(e),
+            NewSessionError::NotW3C(..) | NewSessionError::SessionNotCreated { .. } => None,
         }
     }
 }
@@ -51,7 +68,62 @@ impl fmt::Display for NewSessionError {
             NewSessionError::Failed(ref e) => write!(f, "{}", e),
             NewSessionError::Lost(ref e) => write!(f, "{}", e),
             NewSessionError::NotW3C(ref e) => write!(f, "{:?}", e),
-            NewSessionError::SessionNotCreated(ref e) => write!(f, "{}", e),
+            NewSessionError::SessionNotCreated { ref message, .. } => write!(f, "{}", message),
+            NewSessionError::Standard(ref e) => write!(f, "{}", e),
+        }
+    }
+}
+
+/// An error reported by the WebDriver server in response to a command.
+///
+/// See [the spec] for details about what each of the fields represent.
+///
+/// [the spec]: https://www.w3.org/TR/webdriver/#handling-errors
+#[derive(Debug)]
+pub struct WebDriverError {
+    /// The WebDriver [error code] returned by the server.
+    ///
+    /// [error code]: https://www.w3.org/TR/webdriver/#dfn-error-code
+    pub error: wderror::ErrorStatus,
+    /// The human-readable message the server gave for the failure.
+    pub message: String,
+    /// The stack trace reported by the server, if any (empty otherwise).
+    pub stacktrace: String,
+    /// Additional, implementation-defined information about the error, if the server gave any.
+    pub data: Option<serde_json::Value>,
+}
+
+impl WebDriverError {
+    /// Create a new error with the given code and message, and no stack trace or data.
+    pub fn new<S: Into<String>>(error: wderror::ErrorStatus, message: S) -> Self {
+        WebDriverError {
+            error,
+            message: message.into(),
+            stacktrace: String::new(),
+            data: None,
+        }
+    }
+}
+
+impl Error for WebDriverError {
+    fn description(&self) -> &str {
+        &self.message
+    }
+}
+
+impl fmt::Display for WebDriverError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.message)
+    }
+}
+
+impl From<wderror::WebDriverError> for WebDriverError {
+    fn from(e: wderror::WebDriverError) -> Self {
+        WebDriverError {
+            error: e.error,
+            message: e.message.into_owned(),
+            stacktrace: e.stack.into_owned(),
+            data: None,
         }
     }
 }
@@ -62,10 +134,11 @@ pub enum CmdError {
     /// A standard WebDriver error occurred.
     ///
     /// See [the spec] for details about what each of these errors represent. Note that for
-    /// convenience `NoSuchElement` has been extracted into its own top-level variant.
+    /// convenience `NoSuchElement` and `NotInteractable` have been extracted into their own
+    /// top-level variants.
     ///
     /// [the spec]: https://www.w3.org/TR/webdriver/#handling-errors
-    Standard(wderror::WebDriverError),
+    Standard(WebDriverError),
 
     /// No element was found matching the given locator.
     ///
@@ -73,7 +146,15 @@ pub enum CmdError {
     /// checking for it in user code.
     ///
     /// ["no such element"]: https://www.w3.org/TR/webdriver/#dfn-no-such-element
-    NoSuchElement(wderror::WebDriverError),
+    NoSuchElement(WebDriverError),
+
+    /// The element cannot be interacted with, for example because it is hidden.
+    ///
+    /// This variant lifts the ["element not interactable"] error variant from `Standard` to
+    /// simplify falling back to other strategies (such as clicking using JavaScript) in user code.
+    ///
+    /// ["element not interactable"]: https://www.w3.org/TR/webdriver/#dfn-element-not-interactable
+    NotInteractable(WebDriverError),
 
     /// A bad URL was encountered during parsing.
     ///
@@ -87,6 +168,12 @@ pub enum CmdError {
     /// The connection to the WebDriver server was lost.
     Lost(IOError),
 
+    /// A local I/O operation failed.
+    ///
+    /// This happens if, for example, writing a download to a local writer fails. Unlike `Lost`,
+    /// it says nothing about the state of the connection to the WebDriver server.
+    Io(IOError),
+
     /// The WebDriver server responded with a non-standard, non-JSON reply.
     NotJson(String),
 
@@ -109,6 +196,12 @@ pub enum CmdError {
 
     /// Could not decode a base64 image
     ImageDecodeError(::base64::DecodeError),
+
+    /// Timed out waiting for a condition to become true.
+    WaitTimeout,
+
+    /// A command did not complete before the deadline given to `fantoccini::with_deadline`.
+    Timeout,
 }
 
 impl CmdError {
@@ -138,26 +231,36 @@ impl Error for CmdError {
         match *self {
             CmdError::Standard(..) => "webdriver returned error",
             CmdError::NoSuchElement(..) => "no element found matching selector",
+            CmdError::NotInteractable(..) => "element not interactable",
             CmdError::BadUrl(..) => "bad url provided",
             CmdError::Failed(..) => "webdriver could not be reached",
             CmdError::Lost(..) => "webdriver connection lost",
+            CmdError::Io(..) => "local i/o failed",
             CmdError::NotJson(..) => "webdriver returned invalid response",
             CmdError::Json(..) => "webdriver returned incoherent response",
             CmdError::NotW3C(..) => "webdriver returned non-conforming response",
             CmdError::InvalidArgument(..) => "invalid argument provided",
             CmdError::ImageDecodeError(..) => "error decoding image",
+            CmdError::WaitTimeout => "timed out waiting",
+            CmdError::Timeout => "command timed out",
         }
     }
 
     fn cause(&self) -> Option<&dyn Error> {
         match *self {
-            CmdError::Standard(ref e) | CmdError::NoSuchElement(ref e) => Some(e),
+            CmdError::Standard(ref e)
+            | CmdError::NoSuchElement(ref e)
+            | CmdError::NotInteractable(ref e) => Some(e),
             CmdError::BadUrl(ref e) => Some(e),
             CmdError::Failed(ref e) => Some(e),
-            CmdError::Lost(ref e) => Some(e),
+            CmdError::Lost(ref e) | CmdError::Io(ref e) => Some(e),
             CmdError::Json(ref e) => Some(e),
             CmdError::ImageDecodeError(ref e) => Some(e),
-            CmdError::NotJson(_) | CmdError::NotW3C(_) | CmdError::InvalidArgument(..) => None,
+            CmdError::NotJson(_)
+            | CmdError::NotW3C(_)
+            | CmdError::InvalidArgument(..)
+            | CmdError::WaitTimeout
+            | CmdError::Timeout => None,
         }
     }
 }
@@ -166,14 +269,18 @@ impl fmt::Display for CmdError {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         write!(f, "{}: ", self.description())?;
         match *self {
-            CmdError::Standard(ref e) | CmdError::NoSuchElement(ref e) => write!(f, "{}", e),
+            CmdError::Standard(ref e)
+            | CmdError::NoSuchElement(ref e)
+            | CmdError::NotInteractable(ref e) => write!(f, "{}", e),
             CmdError::BadUrl(ref e) => write!(f, "{}", e),
             CmdError::Failed(ref e) => write!(f, "{}", e),
-            CmdError::Lost(ref e) => write!(f, "{}", e),
+            CmdError::Lost(ref e) | CmdError::Io(ref e) => write!(f, "{}", e),
             CmdError::NotJson(ref e) => write!(f, "{}", e),
             CmdError::Json(ref e) => write!(f, "{}", e),
             CmdError::NotW3C(ref e) => write!(f, "{:?}", e),
             CmdError::ImageDecodeError(ref e) => write!(f, "{:?}", e),
+            CmdError::WaitTimeout => write!(f, "condition was not met in time"),
+            CmdError::Timeout => write!(f, "deadline expired before the command completed"),
             CmdError::InvalidArgument(ref arg, ref msg) => {
                 write!(f, "Invalid argument `{}`: {}", arg, msg)
             }
@@ -201,14 +308,16 @@ impl From<herror::Error> for CmdError {
 
 impl From<wderror::WebDriverError> for CmdError {
     fn from(e: wderror::WebDriverError) -> Self {
-        if let wderror::WebDriverError {
-            error: wderror::ErrorStatus::NoSuchElement,
-            ..
-        } = e
-        {
-            CmdError::NoSuchElement(e)
-        } else {
-            CmdError::Standard(e)
+        WebDriverError::from(e).into()
+    }
+}
+
+impl From<WebDriverError> for CmdError {
+    fn from(e: WebDriverError) -> Self {
+        match e.error {
+            wderror::ErrorStatus::NoSuchElement => CmdError::NoSuchElement(e),
+            wderror::ErrorStatus::ElementNotInteractable => CmdError::NotInteractable(e),
+            _ => CmdError::Standard(e),
         }
     }
 }
@@ -175,16 +175,25 @@ extern crate hyper_tls;
 extern crate mime;
 extern crate serde;
 extern crate serde_json;
+extern crate time;
 extern crate tokio;
 extern crate url;
 extern crate webdriver;
+extern crate zip;
+
+#[cfg(test)]
+#[macro_use]
+extern crate serde_derive;
 
 use http::HttpTryFrom;
 use serde_json::Value as Json;
+use std::io;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
 use tokio::prelude::*;
 use webdriver::command::{SendKeysParameters, WebDriverCommand};
 use webdriver::common::ELEMENT_KEY;
-use webdriver::error::WebDriverError;
+use error::WebDriverError;
 
 macro_rules! via_json {
     ($x:expr) => {{
@@ -192,14 +201,41 @@ macro_rules! via_json {
     }};
 }
 
+pub use cookie::Cookie;
 pub use hyper::Method;
 
+/// Convenience accessors for the `Cookie`s returned by `Client::get_all_cookies`.
+pub trait CookieExt {
+    /// The time at which the cookie expires, or `None` if it is a session cookie.
+    ///
+    /// WebDriver reports cookie expiry as whole seconds since the Unix epoch, so this saves
+    /// converting `Cookie::expires` by hand.
+    fn expiry_time(&self) -> Option<SystemTime>;
+}
+
+impl<'c> CookieExt for Cookie<'c> {
+    fn expiry_time(&self) -> Option<SystemTime> {
+        let at = self.expires()?.to_timespec();
+        if at.sec < 0 {
+            return None;
+        }
+        Some(UNIX_EPOCH + Duration::new(at.sec as u64, at.nsec as u32))
+    }
+}
+
 /// Error types.
 pub mod error;
 
 /// The long-running session future we spawn for multiplexing onto a running WebDriver instance.
 mod session;
-use session::{Cmd, Session};
+use session::{Cmd, ExtensionCommand, Session};
+
+/// Chains of low-level input actions, such as mouse movement and key presses.
+mod actions;
+pub use actions::ActionChain;
+
+/// Packing of Firefox profile directories.
+mod profile;
 
 /// An element locator.
 ///
@@ -210,6 +246,9 @@ pub enum Locator<'a> {
     Css(&'a str),
 
     /// Find an element using the given `id`
+    ///
+    /// The `id` is escaped, so it may contain characters that are special in CSS selectors (such
+    /// as `:` or `.`).
     Id(&'a str),
 
     /// Find a link element with the given link text.
@@ -219,6 +258,72 @@ pub enum Locator<'a> {
 
     /// Find an element using the given XPath expression.
     XPath(&'a str),
+
+    /// Find an element with the given tag name (e.g., `a` or `input`).
+    ///
+    /// Since the W3C specification dropped the native tag name strategy, this is translated into
+    /// the equivalent CSS selector.
+    TagName(&'a str),
+
+    /// Find an element with the given class.
+    ///
+    /// Only a single class name is supported; looking up a name that contains whitespace (e.g.,
+    /// `"foo bar"`) fails with `CmdError::InvalidArgument`. The name is escaped, so it may contain
+    /// characters that are special in CSS selectors.
+    ClassName(&'a str),
+}
+
+impl<'a> Locator<'a> {
+    /// Check that this locator can be sent to the server as-is.
+    fn check(&self) -> Result<(), error::CmdError> {
+        match *self {
+            Locator::ClassName(s) if s.is_empty() || s.contains(char::is_whitespace) => {
+                Err(error::CmdError::InvalidArgument(
+                    "ClassName".to_string(),
+                    format!("{:?} is not a single class name", s),
+                ))
+            }
+            _ => Ok(()),
+        }
+    }
+
+    /// Check this locator and serialize it into the request body that is sent to the WebDriver
+    /// server, so that it can be reused without redoing that work every time.
+    ///
+    /// See `Client::find_compiled`.
+    pub fn compile(self) -> Result<CompiledLocator, error::CmdError> {
+        self.check()?;
+        let locator: webdriver::command::LocatorParameters = self.into();
+        let body = serde_json::to_string(&locator)?;
+        Ok(CompiledLocator {
+            using: locator.using,
+            value: locator.value,
+            body: body.into(),
+        })
+    }
+}
+
+/// A `Locator` that has been prepared for repeated use with `Locator::compile`.
+///
+/// Unlike `Locator`, this owns its selector, so it can be kept around independently of where the
+/// selector came from.
+#[derive(Clone)]
+pub struct CompiledLocator {
+    using: webdriver::common::LocatorStrategy,
+    value: String,
+    /// The JSON body of the find request, which is sent as-is for every lookup.
+    body: Arc<str>,
+}
+
+impl CompiledLocator {
+    /// How elements found with this locator can be found again if they go stale.
+    fn origin(&self) -> Origin {
+        Origin {
+            using: self.using.clone(),
+            value: self.value.clone(),
+            parent: None,
+        }
+    }
 }
 
 impl<'a> Into<webdriver::command::LocatorParameters> for Locator<'a> {
@@ -229,8 +334,8 @@ impl<'a> Into<webdriver::command::LocatorParameters> for Locator<'a> {
                 value: s.to_string(),
             },
             Locator::Id(s) => webdriver::command::LocatorParameters {
-                using: webdriver::common::LocatorStrategy::XPath,
-                value: format!("//*[@id=\"{}\"]", s),
+                using: webdriver::common::LocatorStrategy::CSSSelector,
+                value: format!("#{}", css_escape(s)),
             },
             Locator::XPath(s) => webdriver::command::LocatorParameters {
                 using: webdriver::common::LocatorStrategy::XPath,
@@ -240,17 +345,293 @@ impl<'a> Into<webdriver::command::LocatorParameters> for Locator<'a> {
                 using: webdriver::common::LocatorStrategy::LinkText,
                 value: s.to_string(),
             },
+            Locator::TagName(s) => webdriver::command::LocatorParameters {
+                using: webdriver::common::LocatorStrategy::CSSSelector,
+                value: s.to_string(),
+            },
+            Locator::ClassName(s) => webdriver::command::LocatorParameters {
+                using: webdriver::common::LocatorStrategy::CSSSelector,
+                value: format!(".{}", css_escape(s)),
+            },
+        }
+    }
+}
+
+/// Escape `ident` so that it can be used as an identifier (e.g., an id) in a CSS selector.
+///
+/// This follows the [`CSS.escape`] algorithm from the CSSOM specification.
+///
+/// [`CSS.escape`]: https://drafts.csswg.org/cssom/#serialize-an-identifier
+fn css_escape(ident: &str) -> String {
+    let mut escaped = String::with_capacity(ident.len());
+    let starts_with_dash = ident.starts_with('-');
+    for (i, c) in ident.chars().enumerate() {
+        match c {
+            '\0' => escaped.push('\u{FFFD}'),
+            '\u{1}'..='\u{1F}' | '\u{7F}' => escaped.push_str(&format!("\\{:x} ", c as u32)),
+            '0'..='9' if i == 0 || (i == 1 && starts_with_dash) => {
+                escaped.push_str(&format!("\\{:x} ", c as u32))
+            }
+            '-' if i == 0 && ident.len() == 1 => escaped.push_str("\\-"),
+            '-' | '_' | '0'..='9' | 'a'..='z' | 'A'..='Z' => escaped.push(c),
+            c if c as u32 >= 0x80 => escaped.push(c),
+            c => {
+                escaped.push('\\');
+                escaped.push(c);
+            }
+        }
+    }
+    escaped
+}
+
+/// A range of pages to print.
+///
+/// See `PrintOptions::page_ranges`.
+#[derive(Clone, Copy, Eq, PartialEq, Debug, Hash)]
+pub enum PageRange {
+    /// A single page (1-indexed).
+    Page(u32),
+
+    /// An inclusive range of pages (1-indexed).
+    ///
+    /// A missing start means "from the first page", and a missing end means "to the last page".
+    Range(Option<u32>, Option<u32>),
+}
+
+/// Options for printing the current page using `Client::print_page`.
+///
+/// See <https://www.w3.org/TR/webdriver/#print-page>.
+#[derive(Clone, PartialEq, Debug)]
+pub struct PrintOptions {
+    /// Print in landscape rather than portrait orientation.
+    pub landscape: bool,
+    /// Print background graphics.
+    pub background: bool,
+    /// The scale of the page rendering; must be between 0.1 and 2.
+    pub scale: f64,
+    /// Shrink the page content to fit the paper width.
+    pub shrink_to_fit: bool,
+    /// The pages to print. All pages are printed if this is empty.
+    pub page_ranges: Vec<PageRange>,
+}
+
+impl Default for PrintOptions {
+    fn default() -> Self {
+        PrintOptions {
+            landscape: false,
+            background: false,
+            scale: 1.0,
+            shrink_to_fit: true,
+            page_ranges: Vec::new(),
+        }
+    }
+}
+
+impl PrintOptions {
+    /// Set `page_ranges` from a comma-separated list of pages and page ranges.
+    ///
+    /// For example, `"1-3,5,8-"` prints pages one through three, page five, and page eight through
+    /// the last page. A range may also be open at the start (`"-3"`).
+    ///
+    /// A `CmdError::InvalidArgument` is returned if `ranges` is malformed, in which case
+    /// `page_ranges` is left unchanged.
+    pub fn page_ranges_from_str(&mut self, ranges: &str) -> Result<(), error::CmdError> {
+        let invalid = |msg: String| error::CmdError::InvalidArgument("ranges".into(), msg);
+        let page = |p: &str| -> Result<Option<u32>, error::CmdError> {
+            let p = p.trim();
+            if p.is_empty() {
+                return Ok(None);
+            }
+            match p.parse::<u32>() {
+                Ok(p) if p > 0 => Ok(Some(p)),
+                _ => Err(invalid(format!("`{}` is not a valid page number", p))),
+            }
+        };
+
+        let mut parsed = Vec::new();
+        for range in ranges.split(',') {
+            let mut ends = range.splitn(2, '-');
+            let start = page(ends.next().unwrap_or(""))?;
+            parsed.push(match ends.next() {
+                None => match start {
+                    Some(p) => PageRange::Page(p),
+                    None => return Err(invalid(format!("empty page range in `{}`", ranges))),
+                },
+                Some(end) => match (start, page(end)?) {
+                    (None, None) => {
+                        return Err(invalid(format!("`{}` is not a valid page range", range)))
+                    }
+                    (Some(s), Some(e)) if s > e => {
+                        return Err(invalid(format!("`{}` is not a valid page range", range)))
+                    }
+                    (start, end) => PageRange::Range(start, end),
+                },
+            });
+        }
+
+        self.page_ranges = parsed;
+        Ok(())
+    }
+
+    /// The JSON parameters for the print command.
+    fn to_json(&self) -> Json {
+        let ranges: Vec<Json> = self
+            .page_ranges
+            .iter()
+            .map(|range| match *range {
+                PageRange::Page(p) => Json::from(p),
+                PageRange::Range(start, end) => Json::from(format!(
+                    "{}-{}",
+                    start.map(|p| p.to_string()).unwrap_or_default(),
+                    end.map(|p| p.to_string()).unwrap_or_default()
+                )),
+            })
+            .collect();
+
+        serde_json::json!({
+            "orientation": if self.landscape { "landscape" } else { "portrait" },
+            "background": self.background,
+            "scale": self.scale,
+            "shrinkToFit": self.shrink_to_fit,
+            "pageRanges": ranges,
+        })
+    }
+}
+
+/// Options for taking a screenshot of an element using `Element::screenshot_opts`.
+#[derive(Clone, PartialEq, Debug)]
+pub struct ScreenshotOptions {
+    /// Scroll the element into view before taking the screenshot.
+    ///
+    /// When this is `false`, only the part of the element that is currently in the viewport is
+    /// captured, and the page is left exactly as it is.
+    pub scroll: bool,
+}
+
+impl Default for ScreenshotOptions {
+    fn default() -> Self {
+        ScreenshotOptions { scroll: true }
+    }
+}
+
+/// The state a browser permission can be put in using `Client::set_permission`.
+///
+/// See <https://w3c.github.io/permissions/#dom-permissionstate>.
+#[derive(Clone, Copy, Eq, PartialEq, Debug, Hash)]
+pub enum PermissionState {
+    /// The permission is granted without prompting the user.
+    Granted,
+    /// The permission is denied without prompting the user.
+    Denied,
+    /// The user is prompted when the permission is requested.
+    Prompt,
+}
+
+impl PermissionState {
+    fn as_str(self) -> &'static str {
+        match self {
+            PermissionState::Granted => "granted",
+            PermissionState::Denied => "denied",
+            PermissionState::Prompt => "prompt",
+        }
+    }
+}
+
+/// The context Firefox runs commands in, as set with `Client::set_context`.
+#[derive(Clone, Copy, Eq, PartialEq, Debug, Hash)]
+pub enum Context {
+    /// Commands apply to the web page, as usual.
+    Content,
+    /// Commands apply to the browser's own user interface, and scripts run with chrome
+    /// privileges.
+    Chrome,
+}
+
+impl Context {
+    fn as_str(self) -> &'static str {
+        match self {
+            Context::Content => "content",
+            Context::Chrome => "chrome",
         }
     }
 }
 
+/// Timing information about how the current page was loaded, as returned by
+/// `Client::navigation_timing`.
+///
+/// All times are in milliseconds, relative to when the navigation started. See
+/// [`PerformanceNavigationTiming`] for the details of what each of them measures.
+///
+/// [`PerformanceNavigationTiming`]: https://www.w3.org/TR/navigation-timing-2/
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct NavigationTiming {
+    /// When the first byte of the response was received.
+    pub response_start: f64,
+    /// When the response was fully received.
+    pub response_end: f64,
+    /// When the document became interactive, i.e., when it was fully parsed.
+    pub dom_interactive: f64,
+    /// When the handlers for the `DOMContentLoaded` event finished running.
+    pub dom_content_loaded: f64,
+    /// When the handlers for the `load` event finished running.
+    ///
+    /// This is zero if the page has not finished loading yet.
+    pub load_event_end: f64,
+}
+
+/// Changes to the position and size of the current window, as made by
+/// `Client::update_window_rect`.
+///
+/// Properties that are `None` are left as they are.
+#[derive(Clone, Copy, Eq, PartialEq, Debug, Default, Hash)]
+pub struct PartialWindowRect {
+    /// The new x coordinate of the window's top-left corner.
+    pub x: Option<i32>,
+    /// The new y coordinate of the window's top-left corner.
+    pub y: Option<i32>,
+    /// The new width of the window.
+    pub width: Option<i32>,
+    /// The new height of the window.
+    pub height: Option<i32>,
+}
+
 pub use session::Client;
 
 /// A single element on the current page.
 #[derive(Clone)]
 pub struct Element {
     c: Client,
-    e: webdriver::common::WebElement,
+    /// Shared between clones, so that a reference refreshed after going stale is used from then on.
+    e: Arc<Mutex<webdriver::common::WebElement>>,
+    origin: Option<Origin>,
+}
+
+/// How an `Element` was found, so that it can be found again if it goes stale.
+#[derive(Clone)]
+struct Origin {
+    using: webdriver::common::LocatorStrategy,
+    value: String,
+    parent: Option<webdriver::common::WebElement>,
+}
+
+impl Origin {
+    /// Look up the element again.
+    fn find(
+        &self,
+        mut c: Client,
+    ) -> impl Future<Item = webdriver::common::WebElement, Error = error::CmdError> {
+        let locator = webdriver::command::LocatorParameters {
+            using: self.using.clone(),
+            value: self.value.clone(),
+        };
+        let command = match self.parent {
+            None => WebDriverCommand::FindElement(locator),
+            Some(ref parent) => WebDriverCommand::FindElementElement(parent.clone(), locator),
+        };
+        let legacy = c.is_legacy();
+        c.issue(command)
+            .and_then(move |res| parse_lookup(res, legacy))
+    }
 }
 
 /// An HTML form on the current page.
@@ -260,11 +641,245 @@ pub struct Form {
     f: webdriver::common::WebElement,
 }
 
+/// A handle to a browser window or tab.
+///
+/// See <https://www.w3.org/TR/webdriver/#dfn-window-handle>.
+#[derive(Clone, Eq, PartialEq, Debug, Hash)]
+pub struct WindowHandle(String);
+
+impl AsRef<str> for WindowHandle {
+    fn as_ref(&self) -> &str {
+        &self.0
+    }
+}
+
+impl From<WindowHandle> for String {
+    fn from(handle: WindowHandle) -> Self {
+        handle.0
+    }
+}
+
+/// The identifier a WebDriver server assigned to a session.
+///
+/// See `Client::session_id` and `Client::attach`.
+#[derive(Clone, Eq, PartialEq, Debug, Hash)]
+pub struct SessionId(String);
+
+impl AsRef<str> for SessionId {
+    fn as_ref(&self) -> &str {
+        &self.0
+    }
+}
+
+impl From<String> for SessionId {
+    fn from(id: String) -> Self {
+        SessionId(id)
+    }
+}
+
+impl From<SessionId> for String {
+    fn from(id: SessionId) -> Self {
+        id.0
+    }
+}
+
+impl std::fmt::Display for SessionId {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(f, "{}", self.0)
+    }
+}
+
+/// The response to an HTTP request issued with `Client::fetch`.
+#[derive(Debug)]
+pub struct RawResponse {
+    inner: hyper::Response<hyper::Body>,
+}
+
+/// The dialect of the WebDriver protocol to speak to the server.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Protocol {
+    /// The [W3C WebDriver] protocol, which all current drivers implement.
+    ///
+    /// [W3C WebDriver]: https://www.w3.org/TR/webdriver/
+    W3C,
+    /// The legacy [JSON Wire protocol], which some older Selenium grids still only speak.
+    ///
+    /// [JSON Wire protocol]: https://github.com/SeleniumHQ/selenium/wiki/JsonWireProtocol
+    Legacy,
+}
+
+/// A builder for configuring how a new `Client` connects to the WebDriver server.
+///
+/// Use `ClientBuilder::connect` to establish the session once configured.
+#[derive(Clone, Debug, Default)]
+pub struct ClientBuilder {
+    capabilities: webdriver::capabilities::Capabilities,
+    headers: hyper::HeaderMap,
+    user_agent: Option<String>,
+    protocol: Option<Protocol>,
+    accept_insecure_certs: bool,
+    firefox_profile: Option<String>,
+    download_directory: Option<String>,
+}
+
+impl ClientBuilder {
+    /// Start configuring a new `Client` with an empty capabilities list and no extra headers.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Request the given capabilities when creating the session.
+    ///
+    /// See `Client::with_capabilities` for how these are sent to the server.
+    pub fn capabilities(&mut self, cap: webdriver::capabilities::Capabilities) -> &mut Self {
+        self.capabilities = cap;
+        self
+    }
+
+    /// Attach the given HTTP header to every request sent to the WebDriver server.
+    ///
+    /// This is useful when the server sits behind, for example, an authenticating proxy. The
+    /// header is only sent on the control channel to the server, not on any requests the browser
+    /// itself makes. Setting the same header more than once sends all the given values.
+    pub fn header<K>(&mut self, name: K, value: hyper::header::HeaderValue) -> &mut Self
+    where
+        K: hyper::header::IntoHeaderName,
+    {
+        self.headers.append(name, value);
+        self
+    }
+
+    /// Send the given `User-Agent` header on every request to the WebDriver server.
+    ///
+    /// Like `header`, this only affects the control channel to the server, and not the requests
+    /// the browser itself makes. It is equivalent to calling `Client::set_ua` right away, except
+    /// that it also applies to the request that creates the session.
+    pub fn user_agent<S: Into<String>>(&mut self, ua: S) -> &mut Self {
+        self.user_agent = Some(ua.into());
+        self
+    }
+
+    /// Only speak the given dialect of the WebDriver protocol to the server.
+    ///
+    /// By default, the W3C protocol is tried first, and the legacy protocol is only used if the
+    /// server's response suggests that it does not understand W3C. With `Protocol::Legacy`, the
+    /// session is created with `desiredCapabilities` right away, and element references are sent
+    /// under the legacy `ELEMENT` key. With `Protocol::W3C`, there is no fallback at all.
+    pub fn protocol(&mut self, protocol: Protocol) -> &mut Self {
+        self.protocol = Some(protocol);
+        self
+    }
+
+    /// Make the browser accept invalid (e.g., self-signed or expired) TLS certificates.
+    ///
+    /// This sets the [`acceptInsecureCerts`] capability, and is mostly useful for testing against
+    /// staging servers.
+    ///
+    /// [`acceptInsecureCerts`]: https://www.w3.org/TR/webdriver/#dfn-insecure-tls-certificates
+    pub fn accept_insecure_certs(&mut self, accept: bool) -> &mut Self {
+        self.accept_insecure_certs = accept;
+        self
+    }
+
+    /// Launch Firefox with a copy of the profile (preferences, extensions, and so on) in the given
+    /// directory.
+    ///
+    /// The directory is zipped and sent along in the `profile` entry of [`moz:firefoxOptions`],
+    /// so it does not need to be accessible to the WebDriver server. Since this happens right
+    /// away, an error is returned if the directory cannot be read.
+    ///
+    /// [`moz:firefoxOptions`]: https://developer.mozilla.org/en-US/docs/Web/WebDriver/Capabilities/firefoxOptions
+    pub fn firefox_profile(&mut self, path: &std::path::Path) -> io::Result<&mut Self> {
+        let zipped = profile::zip_profile(path)?;
+        self.firefox_profile = Some(base64::encode(&zipped));
+        Ok(self)
+    }
+
+    /// Make the browser save downloaded files to the given directory, without prompting.
+    ///
+    /// This sets the relevant preferences in [`moz:firefoxOptions`] and [`goog:chromeOptions`].
+    /// On Chromium, the download directory can also be changed once the session is running with
+    /// `Client::set_download_directory`. Relative paths are resolved against the current working
+    /// directory, and the directory must be accessible to the machine the browser runs on.
+    ///
+    /// [`moz:firefoxOptions`]: https://developer.mozilla.org/en-US/docs/Web/WebDriver/Capabilities/firefoxOptions
+    /// [`goog:chromeOptions`]: https://chromedriver.chromium.org/capabilities
+    pub fn download_directory(&mut self, dir: &std::path::Path) -> &mut Self {
+        let dir = std::env::current_dir()
+            .map(|cwd| cwd.join(dir))
+            .unwrap_or_else(|_| dir.to_path_buf());
+        self.download_directory = Some(dir.to_string_lossy().into_owned());
+        self
+    }
+
+    /// Create a new `Client` associated with a new WebDriver session on the server at the given
+    /// URL, using the configuration in this builder.
+    ///
+    /// See `Client::with_capabilities` for details.
+    pub fn connect(
+        &self,
+        webdriver: &str,
+    ) -> impl Future<Item = Client, Error = error::NewSessionError> {
+        let mut cap = self.capabilities.clone();
+        if self.accept_insecure_certs {
+            cap.insert("acceptInsecureCerts".to_string(), Json::from(true));
+        }
+        if let Some(ref profile) = self.firefox_profile {
+            cap.entry("moz:firefoxOptions".to_string())
+                .or_insert_with(|| Json::Object(serde_json::Map::new()))
+                .as_object_mut()
+                .expect("moz:firefoxOptions wasn't a JSON object")
+                .insert("profile".to_string(), Json::from(profile.clone()));
+        }
+        if let Some(ref dir) = self.download_directory {
+            let firefox = serde_json::json!({
+                // 2 means "use browser.download.dir"
+                "browser.download.folderList": 2,
+                "browser.download.dir": dir,
+                "browser.download.useDownloadDir": true,
+                "browser.helperApps.neverAsk.saveToDisk":
+                    "application/octet-stream,application/pdf,application/zip,text/plain,text/csv",
+            });
+            let chrome = serde_json::json!({
+                "download.default_directory": dir,
+                "download.prompt_for_download": false,
+            });
+            merge_prefs(&mut cap, "moz:firefoxOptions", firefox);
+            merge_prefs(&mut cap, "goog:chromeOptions", chrome);
+        }
+        Session::with_capabilities(
+            webdriver,
+            cap,
+            self.headers.clone(),
+            self.user_agent.clone(),
+            self.protocol,
+        )
+    }
+}
+
+/// Add the given preferences to the `prefs` entry of the browser-specific `options` capability.
+fn merge_prefs(cap: &mut webdriver::capabilities::Capabilities, options: &str, prefs: Json) {
+    let existing = cap
+        .entry(options.to_string())
+        .or_insert_with(|| Json::Object(serde_json::Map::new()))
+        .as_object_mut()
+        .expect("browser options weren't a JSON object")
+        .entry("prefs".to_string())
+        .or_insert_with(|| Json::Object(serde_json::Map::new()))
+        .as_object_mut()
+        .expect("browser prefs weren't a JSON object");
+    if let Json::Object(prefs) = prefs {
+        existing.extend(prefs);
+    }
+}
+
 impl Client {
     /// Create a new `Client` associated with a new WebDriver session on the server at the given
     /// URL.
     ///
-    /// Calls `with_capabilities` with an empty capabilities list.
+    /// Calls `with_capabilities` with an empty capabilities list, so the driver picks its own
+    /// defaults (for example, whether the browser runs headless). This is the simplest way to get
+    /// started, such as with `Client::new("http://localhost:4444")` for a local `geckodriver`.
     #[cfg_attr(feature = "cargo-clippy", allow(new_ret_no_self))]
     pub fn new(webdriver: &str) -> impl Future<Item = Self, Error = error::NewSessionError> {
         Self::with_capabilities(webdriver, webdriver::capabilities::Capabilities::new())
@@ -288,7 +903,7 @@ impl Client {
         webdriver: &str,
         cap: webdriver::capabilities::Capabilities,
     ) -> impl Future<Item = Self, Error = error::NewSessionError> {
-        Session::with_capabilities(webdriver, cap)
+        ClientBuilder::new().capabilities(cap).connect(webdriver)
     }
 
     /// Create a new `Client` associated a exist WebDriver session
@@ -296,18 +911,63 @@ impl Client {
         webdriver: &str,
         session: &str,
     ) -> impl Future<Item = Self, Error = error::NewSessionError> {
-        Session::with_session_id(webdriver, session.to_owned())
+        Session::with_session_id(webdriver, session.to_owned(), None)
+    }
+
+    /// Create a new `Client` bound to an already-existing WebDriver session on the server at the
+    /// given URL, without creating a new session.
+    ///
+    /// This is useful for inspecting a session that was created elsewhere, for example by another
+    /// process that called `persist`. `capabilities` should be the capabilities the server
+    /// reported when the session was created, which lets the client adapt to the browser in use.
+    /// If they are not known, pass `Json::Null`; any value that is not a JSON object is treated
+    /// the same way.
+    ///
+    /// Note that, just like any other `Client`, the session is closed when the last instance of
+    /// the returned `Client` is dropped, unless `persist` is called.
+    pub fn attach(
+        webdriver: &str,
+        session_id: SessionId,
+        capabilities: Json,
+    ) -> impl Future<Item = Self, Error = error::NewSessionError> {
+        let caps = match capabilities {
+            Json::Object(caps) => Some(caps),
+            _ => None,
+        };
+        Session::with_session_id(webdriver, session_id.0, caps)
     }
 
     /// Get the session ID assigned by the WebDriver server to this client.
-    pub fn session_id(&mut self) -> impl Future<Item = Option<String>, Error = error::CmdError> {
+    pub fn session_id(
+        &mut self,
+    ) -> impl Future<Item = Option<SessionId>, Error = error::CmdError> {
         self.issue(Cmd::GetSessionId).map(|v| match v {
-            Json::String(s) => Some(s),
+            Json::String(s) => Some(SessionId(s)),
             Json::Null => None,
             v => unreachable!("response to GetSessionId was not a string: {:?}", v),
         })
     }
 
+    /// Get the address of the browser's remote debugger, if it exposes one.
+    ///
+    /// This can be used to attach external tools, such as the DevTools, to the browser. The
+    /// address is read from the capabilities the WebDriver server reported when the session was
+    /// created: `goog:chromeOptions.debuggerAddress` on Chromium, and `moz:debuggerAddress` on
+    /// Firefox (which only exposes it if that capability was set to `true` when connecting).
+    /// `None` is returned if the server did not report an address.
+    pub fn debugger_address(&self) -> Option<String> {
+        let caps = self.capabilities()?;
+        let chromium = ["goog:chromeOptions", "ms:edgeOptions"]
+            .iter()
+            .filter_map(|opts| caps.get(*opts))
+            .filter_map(|opts| opts.get("debuggerAddress"))
+            .next();
+        caps.get("moz:debuggerAddress")
+            .or(chromium)
+            .and_then(|addr| addr.as_str())
+            .map(String::from)
+    }
+
     /// Set the User Agent string to use for all subsequent requests.
     pub fn set_ua<S: Into<String>>(
         &mut self,
@@ -316,6 +976,37 @@ impl Client {
         self.issue(Cmd::SetUA(ua.into())).map(|_| ())
     }
 
+    /// Send commands that only read state again if the connection to the WebDriver server is
+    /// reset before they complete.
+    ///
+    /// Long-lived sessions occasionally lose their connection to the WebDriver server, which
+    /// otherwise surfaces as a `CmdError::Failed` error. When enabled, commands that are sent as
+    /// HTTP `GET` requests (such as `current_url` or `source`) are retried once, over a fresh
+    /// connection. Commands that may change state, such as clicks and navigation, are never
+    /// retried, since the server may already have carried them out.
+    ///
+    /// This is disabled by default, and the setting is shared by all clones of this `Client`.
+    pub fn retry_on_connection_reset(&mut self, retry: bool) {
+        self.set_retry_on_reset(retry);
+    }
+
+    /// Register a function to be called after each WebDriver command completes.
+    ///
+    /// The function is given the name of the command's endpoint (such as `element` or
+    /// `element/{element id}/click`) and how long the command took, which makes it easy to
+    /// collect metrics about the session. It is called whether or not the command succeeded, and
+    /// replaces any previously registered function.
+    pub fn set_command_observer<F>(
+        &mut self,
+        observer: F,
+    ) -> impl Future<Item = (), Error = error::CmdError>
+    where
+        F: Fn(&str, Duration) + Send + Sync + 'static,
+    {
+        let observer = session::Observer(std::sync::Arc::new(observer));
+        self.issue(Cmd::SetObserver(observer)).map(|_| ())
+    }
+
     /// Get the current User Agent string.
     pub fn get_ua(&mut self) -> impl Future<Item = Option<String>, Error = error::CmdError> {
         self.issue(Cmd::GetUA).map(|v| match v {
@@ -325,6 +1016,38 @@ impl Client {
         })
     }
 
+    /// Make commands on elements recover from [stale] element references.
+    ///
+    /// When enabled, if a command on an `Element` (such as `click` or `text`) fails because the
+    /// element has gone stale, the element is found again using the locator it was originally
+    /// found with, and the command is retried once. This helps with pages that re-render parts of
+    /// the DOM between when an element is found and when it is used. Elements returned by
+    /// `find_all` cannot be found again, and so are not retried.
+    ///
+    /// This is disabled by default, and the setting is shared by all clones of this `Client`.
+    ///
+    /// [stale]: https://www.w3.org/TR/webdriver/#dfn-stale-element-reference
+    pub fn retry_stale_elements(&mut self, retry: bool) {
+        self.set_retry_stale_elements(retry);
+    }
+
+    /// Send the requests made by `raw_client_for` (and the methods built on it, such as `fetch`)
+    /// through the same HTTP proxy as the browser.
+    ///
+    /// By default, raw requests go straight to their destination, even if the session was created
+    /// with a [`proxy`] capability, so the server may see them differently from the browser's own
+    /// requests. When enabled, raw requests go through the `httpProxy` of a `manual` proxy
+    /// configuration instead. Only `http` URLs can be sent through the proxy; raw requests for
+    /// other URLs then fail with `CmdError::InvalidArgument`. Sessions that do not use a manual
+    /// proxy are not affected.
+    ///
+    /// This is disabled by default, and the setting is shared by all clones of this `Client`.
+    ///
+    /// [`proxy`]: https://www.w3.org/TR/webdriver/#proxy
+    pub fn proxy_raw_requests(&mut self, proxy: bool) {
+        self.set_proxy_raw_requests(proxy);
+    }
+
     /// Terminate the WebDriver session.
     ///
     /// Normally, a shutdown of the WebDriver connection will be initiated when the last clone of a
@@ -341,6 +1064,68 @@ impl Client {
         self.issue(Cmd::Shutdown).map(|_| ())
     }
 
+    /// Terminate the WebDriver session, and make sure the browser process exits as well.
+    ///
+    /// In some setups, the browser started by the driver keeps running after `close`. On
+    /// Chromium, this first asks the browser to exit using the DevTools `Browser.close` command,
+    /// and then ends the session like `close` does. Elsewhere, it is equivalent to `close`.
+    pub fn quit_browser(&mut self) -> impl Future<Item = (), Error = error::CmdError> {
+        if !self.is_chromium() {
+            return future::Either::B(self.close());
+        }
+
+        let mut this = self.clone();
+        future::Either::A(
+            self.cdp("Browser.close", serde_json::json!({}))
+                // the browser may well exit before it gets around to responding
+                .then(move |_| this.close()),
+        )
+    }
+
+    /// Tear down the current WebDriver session, and replace it with a fresh one.
+    ///
+    /// The new session is created with the same capabilities that were originally requested for
+    /// this `Client`, using the same protocol dialect as the original session. All clones of this
+    /// `Client` will issue their commands to the new session from then on, and see the
+    /// capabilities the server reported for it. Clients attached to an existing session with
+    /// `Client::attach` do not know what capabilities to ask for, and fail with a
+    /// `CmdError::Standard` error whose status is `ErrorStatus::UnsupportedOperation`.
+    pub fn restart_session(mut self) -> impl Future<Item = Self, Error = error::CmdError> {
+        let cmd = match self.new_session_cmd() {
+            Some(cmd) => cmd,
+            None => {
+                let e = WebDriverError::new(
+                    webdriver::error::ErrorStatus::UnsupportedOperation,
+                    "cannot restart a session that was not created by this client",
+                );
+                return future::Either::B(future::err(error::CmdError::Standard(e)));
+            }
+        };
+
+        future::Either::A(
+            self.issue(Cmd::EndSession)
+                // the old session may well be broken beyond repair, so failing to delete it should
+                // not stop us from starting over
+                .then(move |_| {
+                    self.issue(cmd).and_then(move |v| {
+                        if v.get("sessionId").and_then(|id| id.as_str()).is_none() {
+                            return Err(error::CmdError::NotW3C(v));
+                        }
+
+                        // w3c puts the negotiated capabilities under "capabilities",
+                        // while legacy implementations put them under "value"
+                        let caps = v
+                            .get("capabilities")
+                            .or_else(|| v.get("value"))
+                            .and_then(|caps| caps.as_object())
+                            .cloned();
+                        self.set_capabilities(caps);
+                        Ok(self)
+                    })
+                }),
+        )
+    }
+
     /// Mark this client's session as persistent.
     ///
     /// After all instances of a `Client` have been dropped, we normally shut down the WebDriver
@@ -408,32 +1193,42 @@ impl Client {
         &mut self,
     ) -> impl Future<Item = (u64, u64, u64, u64), Error = error::CmdError> {
         self.issue(WebDriverCommand::GetWindowRect)
-            .and_then(|v| match v {
-                Json::Object(mut obj) => {
-                    let x = match obj.remove("x").and_then(|x| x.as_u64()) {
-                        Some(x) => x,
-                        None => return Err(error::CmdError::NotW3C(Json::Object(obj))),
-                    };
+            .and_then(parse_window_rect)
+    }
 
-                    let y = match obj.remove("y").and_then(|y| y.as_u64()) {
-                        Some(y) => y,
-                        None => return Err(error::CmdError::NotW3C(Json::Object(obj))),
-                    };
+    /// Change only some of the x, y, width, and height properties of the current window.
+    ///
+    /// The properties that are not set in `rect` are read from the current window first, and
+    /// preserved. As with `set_window_rect`, all values must be `>= 0` or you will get a
+    /// `CmdError::InvalidArgument`.
+    pub fn update_window_rect(
+        &mut self,
+        rect: PartialWindowRect,
+    ) -> impl Future<Item = (), Error = error::CmdError> {
+        let mut this = self.clone();
+        self.get_window_rect()
+            .and_then(move |(x, y, width, height)| {
+                this.set_window_rect(
+                    rect.x.unwrap_or(x as i32),
+                    rect.y.unwrap_or(y as i32),
+                    rect.width.unwrap_or(width as i32),
+                    rect.height.unwrap_or(height as i32),
+                )
+            })
+    }
 
-                    let width = match obj.remove("width").and_then(|width| width.as_u64()) {
-                        Some(width) => width,
-                        None => return Err(error::CmdError::NotW3C(Json::Object(obj))),
-                    };
-
-                    let height = match obj.remove("height").and_then(|height| height.as_u64()) {
-                        Some(height) => height,
-                        None => return Err(error::CmdError::NotW3C(Json::Object(obj))),
-                    };
-
-                    Ok((x, y, width, height))
-                }
-                _ => Err(error::CmdError::NotW3C(v)),
-            })
+    /// Minimize the current window.
+    ///
+    /// Returns the x, y, width, and height properties of the window as reported by the driver
+    /// once it has been minimized. Many drivers report a zero size for minimized windows, though
+    /// some (especially when running headless) leave the window's geometry unchanged.
+    ///
+    /// See <https://www.w3.org/TR/webdriver/#minimize-window>.
+    pub fn minimize_window(
+        &mut self,
+    ) -> impl Future<Item = (u64, u64, u64, u64), Error = error::CmdError> {
+        self.issue(WebDriverCommand::MinimizeWindow)
+            .and_then(parse_window_rect)
     }
 
     /// Sets the x, y, width, and height properties of the current window.
@@ -489,6 +1284,27 @@ impl Client {
             })
     }
 
+    /// Gets the width and height of the viewport of the current window.
+    ///
+    /// Unlike `get_window_size`, this excludes the browser's own user interface, such as the tab
+    /// bar and the address bar, and so gives the space that is available to the page itself.
+    pub fn viewport_size(&mut self) -> impl Future<Item = (u64, u64), Error = error::CmdError> {
+        self.execute("return [window.innerWidth, window.innerHeight]", vec![])
+            .and_then(|v| {
+                let size = v.as_array().and_then(|s| {
+                    if s.len() == 2 {
+                        Some((s[0].as_u64()?, s[1].as_u64()?))
+                    } else {
+                        None
+                    }
+                });
+                match size {
+                    Some(size) => Ok(size),
+                    None => Err(error::CmdError::NotW3C(v)),
+                }
+            })
+    }
+
     /// Sets the x, y, width, and height properties of the current window.
     ///
     /// All values must be `>= 0` or you will get a `CmdError::InvalidArgument`.
@@ -557,6 +1373,140 @@ impl Client {
             })
     }
 
+    /// Navigate to the given URL, and wait for the current URL to stop changing.
+    ///
+    /// Unlike `goto`, which resolves as soon as the page has loaded, this waits until the current
+    /// URL has stayed the same for `stable_for`. This protects against client-side redirect
+    /// chains, which many single-page applications go through on load. If the URL has not settled
+    /// within `timeout` of the page loading, `CmdError::WaitTimeout` is returned.
+    pub fn goto_wait_stable(
+        self,
+        url: &str,
+        stable_for: Duration,
+        timeout: Duration,
+    ) -> impl Future<Item = Self, Error = error::CmdError> {
+        // how long to wait between checks of the current URL
+        let poll = std::cmp::min(stable_for, Duration::from_millis(50));
+        self.goto(url)
+            .and_then(|mut c| c.current_url_().map(move |url| (c, url)))
+            .and_then(move |(c, url)| {
+                let start = (c, url, Instant::now());
+                let wait = future::loop_fn(start, move |(mut c, last, since)| {
+                    sleep(poll)
+                        .and_then(move |_| c.current_url_().map(move |url| (c, url)))
+                        .map(move |(c, url)| {
+                            if url != last {
+                                future::Loop::Continue((c, url, Instant::now()))
+                            } else if since.elapsed() >= stable_for {
+                                future::Loop::Break(c)
+                            } else {
+                                future::Loop::Continue((c, last, since))
+                            }
+                        })
+                });
+                wait_with_timeout(wait, timeout)
+            })
+    }
+
+    /// Get timing information about how the current page was loaded.
+    ///
+    /// This reads the [navigation timing] entry of the page's performance timeline, which is
+    /// useful for catching performance regressions. Pages without such an entry (e.g.,
+    /// `about:blank` in some browsers) give `None`.
+    ///
+    /// [navigation timing]: https://www.w3.org/TR/navigation-timing-2/
+    pub fn navigation_timing(
+        &mut self,
+    ) -> impl Future<Item = Option<NavigationTiming>, Error = error::CmdError> {
+        let script = "\
+                      var t = performance.getEntriesByType('navigation')[0];\
+                      if (!t) { return null; }\
+                      return [t.responseStart, t.responseEnd, t.domInteractive,\
+                              t.domContentLoadedEventEnd, t.loadEventEnd];";
+        self.execute(script, vec![]).and_then(|v| {
+            if v.is_null() {
+                return Ok(None);
+            }
+            let timing = v.as_array().and_then(|t| {
+                if t.len() == 5 {
+                    Some(NavigationTiming {
+                        response_start: t[0].as_f64()?,
+                        response_end: t[1].as_f64()?,
+                        dom_interactive: t[2].as_f64()?,
+                        dom_content_loaded: t[3].as_f64()?,
+                        load_event_end: t[4].as_f64()?,
+                    })
+                } else {
+                    None
+                }
+            });
+            match timing {
+                Some(timing) => Ok(Some(timing)),
+                None => Err(error::CmdError::NotW3C(v)),
+            }
+        })
+    }
+
+    /// Navigate to the given URL by submitting a POST request with the given form `fields`.
+    ///
+    /// This injects a hidden form into the current page, populates it with a hidden input for
+    /// each `(name, value)` pair in `fields`, and then submits it. The returned future resolves
+    /// once the browser has left the current page, i.e., once the injected form is gone. If that
+    /// does not happen within `timeout` (for example because a `beforeunload` handler kept the
+    /// browser on the page), `CmdError::WaitTimeout` is returned.
+    ///
+    /// Note that since the form is injected into the current page, a page must already be loaded
+    /// (`about:blank` will do).
+    pub fn navigate_post(
+        mut self,
+        url: &str,
+        fields: &[(&str, &str)],
+        timeout: Duration,
+    ) -> impl Future<Item = Self, Error = error::CmdError> {
+        let url = url.to_owned();
+        let fields: Vec<Json> = fields
+            .iter()
+            .map(|&(name, value)| Json::from(vec![name, value]))
+            .collect();
+        self.current_url_()
+            .and_then(move |base| Ok(base.join(&url)?))
+            .and_then(move |url| {
+                // see the comment in Form::submit_direct for why we don't call form.submit()
+                let script = "\
+                              var f = document.createElement('form');\
+                              f.setAttribute('id', 'fantoccini-navigate-post');\
+                              f.setAttribute('method', 'POST');\
+                              f.setAttribute('action', arguments[0]);\
+                              f.style.display = 'none';\
+                              for (var i = 0; i < arguments[1].length; i++) {\
+                                var h = document.createElement('input');\
+                                h.setAttribute('type', 'hidden');\
+                                h.setAttribute('name', arguments[1][i][0]);\
+                                h.value = arguments[1][i][1];\
+                                f.appendChild(h);\
+                              }\
+                              document.body.appendChild(f);\
+                              document.createElement('form').submit.call(f)";
+                self.execute(script, vec![Json::from(url.into_string()), Json::from(fields)])
+                    .map(move |_| self)
+            })
+            .and_then(move |this| {
+                let wait = future::loop_fn(this, |mut this| {
+                    let script =
+                        "return document.getElementById('fantoccini-navigate-post') === null";
+                    this.execute(script, vec![]).and_then(move |done| {
+                        if done.as_bool().unwrap_or(false) {
+                            return future::Either::A(future::ok(future::Loop::Break(this)));
+                        }
+                        future::Either::B(
+                            sleep(POLL_INTERVAL).map(move |_| future::Loop::Continue(this)),
+                        )
+                    })
+                });
+                wait_with_timeout(wait, timeout)
+            })
+    }
+
     fn current_url_(&mut self) -> impl Future<Item = url::Url, Error = error::CmdError> {
         self.issue(WebDriverCommand::GetCurrentUrl).and_then(|url| {
             if let Some(url) = url.as_str() {
@@ -584,6 +1534,134 @@ impl Client {
             })
     }
 
+    /// Save a PNG-encoded screenshot of the current page to the file at `path`, replacing it if
+    /// it already exists.
+    ///
+    /// Like `save_source`, this fails with `CmdError::Io` if the file cannot be written.
+    pub fn save_screenshot(
+        &mut self,
+        path: &std::path::Path,
+    ) -> impl Future<Item = (), Error = error::CmdError> {
+        let path = path.to_path_buf();
+        self.screenshot().and_then(move |png| write_file(path, png))
+    }
+
+    /// Navigate to the given URL, and get a PNG-encoded screenshot of the page once it has loaded.
+    ///
+    /// This is a shorthand for `goto` followed by `screenshot`, which is handy for smoke tests.
+    pub fn goto_screenshot(
+        &mut self,
+        url: &str,
+    ) -> impl Future<Item = Vec<u8>, Error = error::CmdError> {
+        self.clone().goto(url).and_then(|mut c| c.screenshot())
+    }
+
+    /// Get a PNG-encoded screenshot of the entire page, including the parts that are scrolled out
+    /// of view.
+    ///
+    /// On Firefox, this uses geckodriver's own full-page screenshot command. On Chromium, it uses
+    /// the DevTools `Page.captureScreenshot` command with `captureBeyondViewport`, clipped to the
+    /// size of the document. Other browsers fail with a `CmdError::Standard` error whose status is
+    /// `ErrorStatus::UnsupportedOperation`.
+    pub fn screenshot_full_page(&mut self) -> impl Future<Item = Vec<u8>, Error = error::CmdError> {
+        if self.is_firefox() {
+            let cmd = WebDriverCommand::Extension(ExtensionCommand::FirefoxFullScreenshot);
+            return future::Either::A(future::Either::A(self.issue(cmd).and_then(|src| {
+                if let Some(src) = src.as_str() {
+                    return base64::decode(src).map_err(|e| error::CmdError::ImageDecodeError(e));
+                }
+
+                Err(error::CmdError::NotW3C(src))
+            })));
+        }
+
+        if !self.is_chromium() {
+            let e = WebDriverError::new(
+                webdriver::error::ErrorStatus::UnsupportedOperation,
+                "full-page screenshots are only supported on Firefox and Chromium",
+            );
+            return future::Either::B(future::err(error::CmdError::Standard(e)));
+        }
+
+        let script = "\
+                      var d = document.documentElement;\
+                      return [0, 0, Math.max(d.scrollWidth, d.clientWidth),\
+                              Math.max(d.scrollHeight, d.clientHeight)]";
+        let shot = self.clipped_screenshot(script, vec![], true);
+        future::Either::A(future::Either::B(shot))
+    }
+
+    /// Get the full accessibility tree of the current page, as seen by assistive technologies.
+    ///
+    /// This returns the raw array of [`AXNode`]s given by the DevTools
+    /// `Accessibility.getFullAXTree` command, and so is only supported on Chromium. Other browsers
+    /// fail with a `CmdError::Standard` error whose status is `ErrorStatus::UnsupportedOperation`.
+    ///
+    /// [`AXNode`]: https://chromedevtools.github.io/devtools-protocol/tot/Accessibility/#type-AXNode
+    pub fn accessibility_tree(&mut self) -> impl Future<Item = Json, Error = error::CmdError> {
+        if !self.is_chromium() {
+            let e = WebDriverError::new(
+                webdriver::error::ErrorStatus::UnsupportedOperation,
+                "the accessibility tree is only available on Chromium",
+            );
+            return future::Either::B(future::err(error::CmdError::Standard(e)));
+        }
+
+        future::Either::A(
+            self.cdp("Accessibility.getFullAXTree", serde_json::json!({}))
+                .and_then(|mut res| {
+                    if res["nodes"].is_array() {
+                        return Ok(std::mem::replace(&mut res["nodes"], Json::Null));
+                    }
+
+                    Err(error::CmdError::NotW3C(res))
+                }),
+        )
+    }
+
+    /// Print the current page, and get back the PDF-encoded result.
+    pub fn print_page(
+        &mut self,
+        options: PrintOptions,
+    ) -> impl Future<Item = Vec<u8>, Error = error::CmdError> {
+        self.issue(WebDriverCommand::Extension(ExtensionCommand::Print(
+            options.to_json(),
+        )))
+        .and_then(|src| {
+            if let Some(src) = src.as_str() {
+                return base64::decode(src).map_err(|e| error::CmdError::ImageDecodeError(e));
+            }
+
+            Err(error::CmdError::NotW3C(src))
+        })
+    }
+
+    /// Print the current page, and save the resulting PDF to the file at `path`, replacing it if
+    /// it already exists.
+    ///
+    /// The page is printed like `print_page` does, and `CmdError::Io` is returned if the PDF
+    /// cannot be written.
+    pub fn save_pdf(
+        &mut self,
+        path: &std::path::Path,
+        options: PrintOptions,
+    ) -> impl Future<Item = (), Error = error::CmdError> {
+        let path = path.to_path_buf();
+        self.print_page(options)
+            .and_then(move |pdf| write_file(path, pdf))
+    }
+
+    /// Get the title of the current page.
+    pub fn title(&mut self) -> impl Future<Item = String, Error = error::CmdError> {
+        self.issue(WebDriverCommand::GetTitle).and_then(|title| {
+            if let Some(title) = title.as_str() {
+                return Ok(title.to_string());
+            }
+
+            Err(error::CmdError::NotW3C(title))
+        })
+    }
+
     /// Get the HTML source for the current page.
     pub fn source(&mut self) -> impl Future<Item = String, Error = error::CmdError> {
         self.issue(WebDriverCommand::GetPageSource).and_then(|src| {
@@ -595,6 +1673,18 @@ impl Client {
         })
     }
 
+    /// Save the current page's source to the file at `path`, replacing it if it already exists.
+    ///
+    /// If the file cannot be written, this fails with `CmdError::Io`.
+    pub fn save_source(
+        &mut self,
+        path: &std::path::Path,
+    ) -> impl Future<Item = (), Error = error::CmdError> {
+        let path = path.to_path_buf();
+        self.source()
+            .and_then(move |src| write_file(path, src.into_bytes()))
+    }
+
     /// Go back to the previous page.
     pub fn back(&mut self) -> impl Future<Item = (), Error = error::CmdError> {
         self.issue(WebDriverCommand::GoBack).map(|_| ())
@@ -605,6 +1695,298 @@ impl Client {
         self.issue(WebDriverCommand::Refresh).map(|_| ())
     }
 
+    /// Get the handle of the window that currently has focus.
+    pub fn window(&mut self) -> impl Future<Item = WindowHandle, Error = error::CmdError> {
+        self.issue(WebDriverCommand::GetWindowHandle)
+            .and_then(parse_window_handle)
+    }
+
+    /// Get the handles of all the windows (and tabs) in this session.
+    pub fn windows(&mut self) -> impl Future<Item = Vec<WindowHandle>, Error = error::CmdError> {
+        self.issue(WebDriverCommand::GetWindowHandles)
+            .and_then(parse_window_handles)
+    }
+
+    /// Switch the focus to the window with the given handle.
+    pub fn switch_to_window(
+        &mut self,
+        window: WindowHandle,
+    ) -> impl Future<Item = (), Error = error::CmdError> {
+        let params = webdriver::command::SwitchToWindowParameters { handle: window.0 };
+        self.issue(WebDriverCommand::SwitchToWindow(params))
+            .map(|_| ())
+    }
+
+    /// Switch the focus to the first window (or tab) whose title satisfies `predicate`.
+    ///
+    /// Each window is switched to in turn to read its title. The handle of the matching window is
+    /// returned; if no window matches, the focus is returned to the window that had it to begin
+    /// with, and `Ok(None)` is returned.
+    pub fn window_with_title<F>(
+        &mut self,
+        predicate: F,
+    ) -> impl Future<Item = Option<WindowHandle>, Error = error::CmdError>
+    where
+        F: Fn(&str) -> bool,
+    {
+        let mut this = self.clone();
+        self.window()
+            .and_then(move |original| this.windows().map(move |handles| (this, original, handles)))
+            .and_then(move |(this, original, handles)| {
+                let start = (this, original, handles.into_iter(), predicate);
+                future::loop_fn(start, |(mut this, original, mut handles, predicate)| {
+                    let handle = match handles.next() {
+                        Some(handle) => handle,
+                        None => {
+                            // nothing matched, so put the focus back where it was
+                            return future::Either::B(
+                                this.switch_to_window(original)
+                                    .map(|_| future::Loop::Break(None)),
+                            );
+                        }
+                    };
+                    future::Either::A(
+                        this.switch_to_window(handle.clone())
+                            .and_then(move |_| this.title().map(move |title| (this, title)))
+                            .map(move |(this, title)| {
+                                if predicate(&title) {
+                                    future::Loop::Break(Some(handle))
+                                } else {
+                                    future::Loop::Continue((this, original, handles, predicate))
+                                }
+                            }),
+                    )
+                })
+            })
+    }
+
+    /// Open a new tab, and switch the focus to it.
+    ///
+    /// The new tab starts out at `about:blank`. Its handle is returned so that you can switch back
+    /// to it later.
+    pub fn new_tab(&mut self) -> impl Future<Item = WindowHandle, Error = error::CmdError> {
+        let mut this = self.clone();
+        let cmd = ExtensionCommand::NewWindow(serde_json::json!({ "type": "tab" }));
+        self.issue(WebDriverCommand::Extension(cmd))
+            .and_then(|res| match res.get("handle") {
+                Some(handle) => parse_window_handle(handle.clone()),
+                None => Err(error::CmdError::NotW3C(res)),
+            })
+            .and_then(move |handle| this.switch_to_window(handle.clone()).map(move |_| handle))
+    }
+
+    /// Install the Firefox add-on at the given path, and return its id.
+    ///
+    /// The path may point to either a packed (`.xpi`) add-on, or, if `temporary` is set, to a
+    /// directory containing an unpacked add-on. Temporary add-ons do not need to be signed, and
+    /// are removed when the browser exits. Relative paths are resolved against the current
+    /// working directory, and the add-on must be accessible to the machine the WebDriver server
+    /// runs on.
+    ///
+    /// This is only supported by geckodriver.
+    pub fn install_addon(
+        &mut self,
+        path: &std::path::Path,
+        temporary: bool,
+    ) -> impl Future<Item = String, Error = error::CmdError> {
+        let path = match absolute_path(path, "path") {
+            Ok(path) => path,
+            Err(e) => return future::Either::B(future::err(e)),
+        };
+
+        let params = serde_json::json!({ "path": path, "temporary": temporary });
+        let cmd = WebDriverCommand::Extension(ExtensionCommand::InstallAddon(params));
+        future::Either::A(self.issue(cmd).and_then(|id| match id {
+            Json::String(id) => Ok(id),
+            v => Err(error::CmdError::NotW3C(v)),
+        }))
+    }
+
+    /// Uninstall the Firefox add-on with the given id, as returned by `install_addon`.
+    ///
+    /// This is only supported by geckodriver.
+    pub fn uninstall_addon(&mut self, id: &str) -> impl Future<Item = (), Error = error::CmdError> {
+        let params = serde_json::json!({ "id": id });
+        self.issue(WebDriverCommand::Extension(ExtensionCommand::UninstallAddon(params)))
+            .map(|_| ())
+    }
+
+    /// Get the context Firefox currently runs commands in.
+    ///
+    /// This is only supported on Firefox. Other browsers fail with a `CmdError::Standard` error
+    /// whose status is `ErrorStatus::UnsupportedOperation`.
+    pub fn context(&mut self) -> impl Future<Item = Context, Error = error::CmdError> {
+        if !self.is_firefox() {
+            let e = WebDriverError::new(
+                webdriver::error::ErrorStatus::UnsupportedOperation,
+                "contexts are only supported on Firefox",
+            );
+            return future::Either::B(future::err(error::CmdError::Standard(e)));
+        }
+
+        let cmd = WebDriverCommand::Extension(ExtensionCommand::GetContext);
+        future::Either::A(self.issue(cmd).and_then(|ctx| match ctx.as_str() {
+            Some("content") => Ok(Context::Content),
+            Some("chrome") => Ok(Context::Chrome),
+            _ => Err(error::CmdError::NotW3C(ctx)),
+        }))
+    }
+
+    /// Set the context Firefox runs subsequent commands in.
+    ///
+    /// In the `Chrome` context, commands such as `execute` apply to the browser window itself
+    /// rather than to the web page, and scripts have access to privileged APIs. This is only
+    /// supported on Firefox. Other browsers fail with a `CmdError::Standard` error whose status is
+    /// `ErrorStatus::UnsupportedOperation`.
+    pub fn set_context(&mut self, ctx: Context) -> impl Future<Item = (), Error = error::CmdError> {
+        if !self.is_firefox() {
+            let e = WebDriverError::new(
+                webdriver::error::ErrorStatus::UnsupportedOperation,
+                "contexts are only supported on Firefox",
+            );
+            return future::Either::B(future::err(error::CmdError::Standard(e)));
+        }
+
+        let params = serde_json::json!({ "context": ctx.as_str() });
+        let cmd = WebDriverCommand::Extension(ExtensionCommand::SetContext(params));
+        future::Either::A(self.issue(cmd).map(|_| ()))
+    }
+
+    /// Close the window that currently has focus.
+    ///
+    /// Note that no other window is focused afterwards, so subsequent commands will fail until
+    /// `switch_to_window` is called. If the last window is closed, the session ends. See also
+    /// `close_window_and_switch`.
+    pub fn close_window(&mut self) -> impl Future<Item = (), Error = error::CmdError> {
+        self.issue(WebDriverCommand::CloseWindow).map(|_| ())
+    }
+
+    /// Close the window that currently has focus, and switch the focus to the first remaining
+    /// window.
+    ///
+    /// The handle of the newly focused window is returned, or `None` if no windows remain (in
+    /// which case the session has ended).
+    pub fn close_window_and_switch(
+        &mut self,
+    ) -> impl Future<Item = Option<WindowHandle>, Error = error::CmdError> {
+        let mut this = self.clone();
+        self.issue(WebDriverCommand::CloseWindow)
+            .and_then(parse_window_handles)
+            .and_then(move |remaining| match remaining.into_iter().next() {
+                Some(first) => future::Either::A(
+                    this.switch_to_window(first.clone())
+                        .map(move |_| Some(first)),
+                ),
+                None => future::Either::B(future::ok(None)),
+            })
+    }
+
+    /// Check whether the window that has focus still exists.
+    ///
+    /// After `close_window`, the focus stays on the closed window, and most commands fail with a
+    /// "no such window" error until `switch_to_window` is called. This lets you detect that case
+    /// up front.
+    pub fn focused_window_valid(&mut self) -> impl Future<Item = bool, Error = error::CmdError> {
+        let mut this = self.clone();
+        self.window().then(move |r| match r {
+            Ok(current) => future::Either::A(
+                this.windows()
+                    .map(move |handles| handles.contains(&current)),
+            ),
+            Err(error::CmdError::Standard(WebDriverError {
+                error: webdriver::error::ErrorStatus::NoSuchWindow,
+                ..
+            })) => future::Either::B(future::ok(false)),
+            Err(e) => future::Either::B(future::err(e)),
+        })
+    }
+
+    /// Switch the focus to the frame contained within the current browsing context at the given
+    /// `index`.
+    ///
+    /// If `index` is `None`, the focus is returned to the top-level browsing context (see also
+    /// `switch_to_default_content`). To enter a frame given its `<iframe>` element, use
+    /// `Element::enter_frame`.
+    pub fn enter_frame(
+        mut self,
+        index: Option<u16>,
+    ) -> impl Future<Item = Self, Error = error::CmdError> {
+        let params = webdriver::command::SwitchToFrameParameters {
+            id: index.map(webdriver::common::FrameId::Short),
+        };
+        self.issue(WebDriverCommand::SwitchToFrame(params))
+            .and_then(move |r| {
+                if r.is_null() || r.as_object().map(|o| o.is_empty()).unwrap_or(false) {
+                    // geckodriver returns {} :(
+                    Ok(self)
+                } else {
+                    Err(error::CmdError::NotW3C(r))
+                }
+            })
+    }
+
+    /// Switch the focus to the frame contained in the first element matching the given CSS
+    /// selector.
+    ///
+    /// This is a shorthand for `find` followed by `Element::enter_frame`.
+    pub fn enter_frame_by_css(
+        mut self,
+        css: &str,
+    ) -> impl Future<Item = Self, Error = error::CmdError> {
+        self.find(Locator::Css(css)).and_then(|e| e.enter_frame())
+    }
+
+    /// Run `f` with the focus on the frame contained in the given element, and return the focus
+    /// to the top-level browsing context afterwards.
+    ///
+    /// The focus is returned even if `f` fails, so that the frame does not leak into later
+    /// commands. If both `f` and returning the focus fail, the error from `f` is given.
+    pub fn in_frame<F, FF>(
+        &mut self,
+        frame: Element,
+        f: F,
+    ) -> impl Future<Item = FF::Item, Error = error::CmdError>
+    where
+        F: FnOnce(&mut Client) -> FF,
+        FF: IntoFuture<Error = error::CmdError>,
+    {
+        let mut c = self.clone();
+        frame
+            .enter_frame()
+            .and_then(move |_| {
+                f(&mut c).into_future().then(move |r| Ok::<_, error::CmdError>((c, r)))
+            })
+            .and_then(|(c, r)| {
+                c.switch_to_default_content().then(move |restored| match (r, restored) {
+                    (Err(e), _) | (Ok(_), Err(e)) => Err(e),
+                    (Ok(v), Ok(_)) => Ok(v),
+                })
+            })
+    }
+
+    /// Switch the focus to the parent of the frame that currently has focus.
+    ///
+    /// If the top-level browsing context already has focus, this does nothing.
+    pub fn enter_parent_frame(mut self) -> impl Future<Item = Self, Error = error::CmdError> {
+        self.issue(WebDriverCommand::SwitchToParentFrame)
+            .and_then(move |r| {
+                if r.is_null() || r.as_object().map(|o| o.is_empty()).unwrap_or(false) {
+                    // geckodriver returns {} :(
+                    Ok(self)
+                } else {
+                    Err(error::CmdError::NotW3C(r))
+                }
+            })
+    }
+
+    /// Return the focus to the top-level browsing context, regardless of how many frames deep
+    /// the focus currently is.
+    ///
+    /// This is equivalent to `enter_frame(None)`.
+    pub fn switch_to_default_content(self) -> impl Future<Item = Self, Error = error::CmdError> {
+        self.enter_frame(None)
+    }
+
     /// Execute the given JavaScript `script` in the current browser session.
     ///
     /// `args` is available to the script inside the `arguments` array. Since `Element` implements
@@ -626,23 +2008,341 @@ impl Client {
         self.issue(WebDriverCommand::ExecuteScript(cmd))
     }
 
-    /// Issue an HTTP request to the given `url` with all the same cookies as the current session.
+    /// Execute the given JavaScript `script` in the current browser session, converting its
+    /// arguments from and its return value into Rust types.
     ///
-    /// Calling this method is equivalent to calling `with_raw_client_for` with an empty closure.
-    pub fn raw_client_for<'a>(
-        self,
-        method: Method,
-        url: &str,
-    ) -> impl Future<Item = hyper::Response<hyper::Body>, Error = error::CmdError> {
-        self.with_raw_client_for(method, url, |mut req| {
-            req.body(hyper::Body::empty()).unwrap()
+    /// `arg` is serialized to JSON and passed to the script as its only argument, `arguments[0]`,
+    /// even if it serializes to an array. Use `execute` to pass several arguments. The value the
+    /// script returns is deserialized into `R`, and a `CmdError::Json` error is returned if it does
+    /// not have the expected shape.
+    pub fn execute_typed<A, R>(
+        &mut self,
+        script: &str,
+        arg: A,
+    ) -> impl Future<Item = R, Error = error::CmdError>
+    where
+        A: serde::Serialize,
+        R: serde::de::DeserializeOwned,
+    {
+        let arg = match serde_json::to_value(arg) {
+            Ok(arg) => arg,
+            Err(e) => return future::Either::B(future::err(error::CmdError::from(e))),
+        };
+        future::Either::A(
+            self.execute(script, vec![arg])
+                .and_then(|v| serde_json::from_value(v).map_err(error::CmdError::from)),
+        )
+    }
+
+    /// Execute the given async JavaScript `script` in the current browser session.
+    ///
+    /// The provided JavaScript has access to `args` through the JavaScript variable `arguments`.
+    /// The `arguments` array also holds an additional element at the end that provides a
+    /// completion callback for the asynchronous code. Whatever is passed to that callback is
+    /// returned as the result of the script.
+    ///
+    /// The script fails with a `CmdError::Standard` error whose status is
+    /// `ErrorStatus::ScriptTimeout` if it does not call the callback within the session's script
+    /// timeout. See `execute_async_timeout` for a way to give up sooner than that.
+    pub fn execute_async(
+        &mut self,
+        script: &str,
+        mut args: Vec<Json>,
+    ) -> impl Future<Item = Json, Error = error::CmdError> {
+        self.fixup_elements(&mut args);
+        let cmd = webdriver::command::JavascriptCommandParameters {
+            script: script.to_string(),
+            args: Some(args),
+        };
+
+        self.issue(WebDriverCommand::ExecuteAsyncScript(cmd))
+    }
+
+    /// Execute the given async JavaScript `script`, and fail with `CmdError::Timeout` if it does
+    /// not complete within `timeout`.
+    ///
+    /// This is useful to fail fast when the session's script timeout is more generous than a
+    /// particular script deserves. Note that, as with `with_deadline`, the browser keeps running
+    /// the script until the session's script timeout expires, which delays subsequent commands.
+    pub fn execute_async_timeout(
+        &mut self,
+        script: &str,
+        args: Vec<Json>,
+        timeout: Duration,
+    ) -> impl Future<Item = Json, Error = error::CmdError> {
+        with_deadline(self.execute_async(script, args), timeout)
+    }
+
+    /// Execute the given JavaScript `script`, which must return an array of elements, and give
+    /// back those elements.
+    ///
+    /// This is useful for custom element queries. If the script returns anything other than an
+    /// array of elements, `CmdError::NotW3C` is returned. Note that a `NodeList`, such as the one
+    /// returned by `querySelectorAll`, must be turned into an array (e.g., with `Array.from`).
+    pub fn execute_elements(
+        &mut self,
+        script: &str,
+        args: Vec<Json>,
+    ) -> impl Future<Item = Vec<Element>, Error = error::CmdError> {
+        let c = self.clone();
+        self.execute(script, args).and_then(move |res| {
+            let legacy = c.is_legacy();
+            match res {
+                Json::Array(elements) => elements
+                    .into_iter()
+                    .map(|e| {
+                        parse_lookup(e, legacy).map(|e| Element {
+                            c: c.clone(),
+                            e: Arc::new(Mutex::new(e)),
+                            origin: None,
+                        })
+                    })
+                    .collect::<Result<Vec<_>, _>>(),
+                v => Err(error::CmdError::NotW3C(v)),
+            }
         })
     }
 
-    /// Build and issue an HTTP request to the given `url` with all the same cookies as the current
-    /// session.
+    /// Read the given [property] from each of the given elements, using a single round-trip to
+    /// the WebDriver server.
     ///
-    /// Before the HTTP request is issued, the given `before` closure will be called with a handle
+    /// This is equivalent to calling `Element::prop` on each element in turn, except that the
+    /// values are returned as-is rather than as strings. The values are given in the same order
+    /// as `elements`, with `Json::Null` for elements that do not have the property.
+    ///
+    /// [property]: https://www.ecma-international.org/ecma-262/5.1/#sec-8.12.1
+    pub fn props_for(
+        &mut self,
+        elements: &[&Element],
+        property: &str,
+    ) -> impl Future<Item = Vec<Json>, Error = error::CmdError> {
+        let script = "\
+                      var prop = arguments[0];\
+                      return Array.prototype.slice.call(arguments, 1).map(function(e) {\
+                        var v = e[prop];\
+                        return v === undefined ? null : v;\
+                      });";
+        let n = elements.len();
+        let mut args = vec![Json::from(property)];
+        args.extend(elements.iter().map(|e| via_json!(&e.web_element())));
+        self.execute(script, args).and_then(move |res| match res {
+            Json::Array(values) => {
+                if values.len() == n {
+                    Ok(values)
+                } else {
+                    Err(error::CmdError::NotW3C(Json::Array(values)))
+                }
+            }
+            v => Err(error::CmdError::NotW3C(v)),
+        })
+    }
+
+    /// Scroll the current page by the given number of CSS pixels, horizontally and vertically.
+    ///
+    /// This calls [`window.scrollBy`], so negative values scroll left and up.
+    ///
+    /// [`window.scrollBy`]: https://developer.mozilla.org/en-US/docs/Web/API/Window/scrollBy
+    pub fn scroll_by(&mut self, dx: f64, dy: f64) -> impl Future<Item = (), Error = error::CmdError> {
+        self.execute(
+            "window.scrollBy(arguments[0], arguments[1]);",
+            vec![Json::from(dx), Json::from(dy)],
+        )
+        .map(|_| ())
+    }
+
+    /// Scroll the current page so that the given point in the document, in CSS pixels, is at the
+    /// top-left corner of the viewport.
+    ///
+    /// This calls [`window.scrollTo`]; the browser stops scrolling at the edges of the document.
+    ///
+    /// [`window.scrollTo`]: https://developer.mozilla.org/en-US/docs/Web/API/Window/scrollTo
+    pub fn scroll_to(&mut self, x: f64, y: f64) -> impl Future<Item = (), Error = error::CmdError> {
+        self.execute(
+            "window.scrollTo(arguments[0], arguments[1]);",
+            vec![Json::from(x), Json::from(y)],
+        )
+        .map(|_| ())
+    }
+
+    /// Find the topmost element at the given point, in CSS pixels relative to the viewport.
+    ///
+    /// This uses [`document.elementFromPoint`], and so is useful for checking what a click at
+    /// that point would hit, for example whether an overlay covers an element. If there is no
+    /// element at that point (e.g., because it lies outside the viewport), this fails with
+    /// `CmdError::NoSuchElement`.
+    ///
+    /// [`document.elementFromPoint`]: https://developer.mozilla.org/en-US/docs/Web/API/Document/elementFromPoint
+    pub fn element_from_point(
+        &mut self,
+        x: f64,
+        y: f64,
+    ) -> impl Future<Item = Element, Error = error::CmdError> {
+        let script = "return document.elementFromPoint(arguments[0], arguments[1])";
+        let c = self.clone();
+        self.execute(script, vec![Json::from(x), Json::from(y)])
+            .and_then(move |res| {
+                if res.is_null() {
+                    let e = WebDriverError::new(
+                        webdriver::error::ErrorStatus::NoSuchElement,
+                        format!("no element at ({}, {})", x, y),
+                    );
+                    return Err(error::CmdError::NoSuchElement(e));
+                }
+
+                let e = parse_lookup(res, c.is_legacy())?;
+                Ok(Element {
+                    c,
+                    e: Arc::new(Mutex::new(e)),
+                    origin: None,
+                })
+            })
+    }
+
+    /// Execute the JavaScript in the file at `path` in the current browser session.
+    ///
+    /// This behaves just like `execute`, with the contents of the file as the script. If the file
+    /// cannot be read, this fails with `CmdError::Io` without issuing any command.
+    pub fn execute_file(
+        &mut self,
+        path: &std::path::Path,
+        args: Vec<Json>,
+    ) -> impl Future<Item = Json, Error = error::CmdError> {
+        match std::fs::read_to_string(path) {
+            Ok(script) => future::Either::A(self.execute(&script, args)),
+            Err(e) => future::Either::B(future::err(error::CmdError::Io(e))),
+        }
+    }
+
+    /// Get all the cookies that are visible to the current page.
+    ///
+    /// Use `CookieExt::expiry_time` to find out when each cookie expires.
+    ///
+    /// See <https://www.w3.org/TR/webdriver/#get-all-cookies>.
+    pub fn get_all_cookies(
+        &mut self,
+    ) -> impl Future<Item = Vec<Cookie<'static>>, Error = error::CmdError> {
+        self.issue(WebDriverCommand::GetCookies)
+            .and_then(|v| match v {
+                Json::Array(cookies) => cookies
+                    .into_iter()
+                    .map(parse_cookie)
+                    .collect::<Result<Vec<_>, _>>(),
+                v => Err(error::CmdError::NotW3C(v)),
+            })
+    }
+
+    /// Get the cookies visible to the current page that belong to `domain` or one of its
+    /// subdomains.
+    ///
+    /// For example, with a `domain` of `example.com`, cookies for `example.com` and
+    /// `www.example.com` are returned, but cookies for `notexample.com` are not.
+    pub fn get_cookies_for_domain(
+        &mut self,
+        domain: &str,
+    ) -> impl Future<Item = Vec<Cookie<'static>>, Error = error::CmdError> {
+        let domain = domain.trim_start_matches('.').to_ascii_lowercase();
+        self.get_all_cookies().map(move |cookies| {
+            cookies
+                .into_iter()
+                .filter(|cookie| {
+                    // cookies that are shared with subdomains have a leading .
+                    let d = match cookie.domain() {
+                        Some(d) => d.trim_start_matches('.').to_ascii_lowercase(),
+                        None => return false,
+                    };
+                    d == domain || d.ends_with(&format!(".{}", domain))
+                })
+                .collect()
+        })
+    }
+
+    /// Set the item with the given `key` in the current origin's `localStorage` to `value`.
+    pub fn set_local_storage_item(
+        &mut self,
+        key: &str,
+        value: &str,
+    ) -> impl Future<Item = (), Error = error::CmdError> {
+        self.set_storage_item("localStorage", key, value)
+    }
+
+    /// Get the item with the given `key` from the current origin's `localStorage`.
+    ///
+    /// `Ok(None)` is returned if there is no such item.
+    pub fn get_local_storage_item(
+        &mut self,
+        key: &str,
+    ) -> impl Future<Item = Option<String>, Error = error::CmdError> {
+        self.get_storage_item("localStorage", key)
+    }
+
+    /// Remove all items from the current origin's `localStorage`.
+    pub fn clear_local_storage(&mut self) -> impl Future<Item = (), Error = error::CmdError> {
+        self.clear_storage("localStorage")
+    }
+
+    /// Set the item with the given `key` in the current tab's `sessionStorage` to `value`.
+    pub fn set_session_storage_item(
+        &mut self,
+        key: &str,
+        value: &str,
+    ) -> impl Future<Item = (), Error = error::CmdError> {
+        self.set_storage_item("sessionStorage", key, value)
+    }
+
+    /// Get the item with the given `key` from the current tab's `sessionStorage`.
+    ///
+    /// `Ok(None)` is returned if there is no such item.
+    pub fn get_session_storage_item(
+        &mut self,
+        key: &str,
+    ) -> impl Future<Item = Option<String>, Error = error::CmdError> {
+        self.get_storage_item("sessionStorage", key)
+    }
+
+    /// Remove all items from the current tab's `sessionStorage`.
+    pub fn clear_session_storage(&mut self) -> impl Future<Item = (), Error = error::CmdError> {
+        self.clear_storage("sessionStorage")
+    }
+
+    /// Delete all the cookies that are visible to the current page.
+    ///
+    /// See <https://www.w3.org/TR/webdriver/#delete-all-cookies>.
+    pub fn delete_all_cookies(&mut self) -> impl Future<Item = (), Error = error::CmdError> {
+        self.issue(WebDriverCommand::DeleteCookies).map(|_| ())
+    }
+
+    /// Reset the browser state of the current page, by deleting all its cookies and clearing
+    /// both its `localStorage` and its `sessionStorage`.
+    ///
+    /// This is useful to isolate tests from one another without starting a new session. Note that
+    /// only the cookies visible to the current page are deleted, and that only the current
+    /// origin's storage is cleared; other origins keep theirs. Pages with an opaque origin, such as
+    /// `data:` URLs, have no storage, and fail with a JavaScript error.
+    pub fn clear_browser_state(&mut self) -> impl Future<Item = (), Error = error::CmdError> {
+        let mut this = self.clone();
+        self.delete_all_cookies().and_then(move |_| {
+            this.execute("window.localStorage.clear(); window.sessionStorage.clear();", vec![])
+                .map(|_| ())
+        })
+    }
+
+    /// Issue an HTTP request to the given `url` with all the same cookies as the current session.
+    ///
+    /// Calling this method is equivalent to calling `with_raw_client_for` with an empty closure.
+    pub fn raw_client_for<'a>(
+        self,
+        method: Method,
+        url: &str,
+    ) -> impl Future<Item = hyper::Response<hyper::Body>, Error = error::CmdError> {
+        self.with_raw_client_for(method, url, |mut req| {
+            req.body(hyper::Body::empty()).unwrap()
+        })
+    }
+
+    /// Build and issue an HTTP request to the given `url` with all the same cookies as the current
+    /// session.
+    ///
+    /// Before the HTTP request is issued, the given `before` closure will be called with a handle
     /// to the `Request` about to be sent.
     pub fn with_raw_client_for<F>(
         mut self,
@@ -748,6 +2448,10 @@ impl Client {
                     );
                 }
 
+                let proxy = match this.raw_proxy(&url) {
+                    Ok(proxy) => proxy,
+                    Err(e) => return future::Either::B(future::err(e)),
+                };
                 if all_ok {
                     let mut req = hyper::Request::builder();
                     req.method(method)
@@ -758,7 +2462,7 @@ impl Client {
                     }
                     let req = before(req);
                     let (tx, rx) = futures::sync::oneshot::channel();
-                    future::Either::A(this.issue(Cmd::Raw { req, rsp: tx }).and_then(move |_| {
+                    future::Either::A(this.issue(Cmd::Raw { req, rsp: tx, proxy }).and_then(move |_| {
                         rx.then(|r| match r {
                             Ok(Ok(r)) => Ok(r),
                             Ok(Err(e)) => Err(e.into()),
@@ -771,12 +2475,66 @@ impl Client {
             })
     }
 
+    /// Stream the response to an HTTP request for the given `url` into `writer`.
+    ///
+    /// Like `raw_client_for`, the request carries all the same cookies as the current session.
+    /// The response body is written to `writer` chunk by chunk as it arrives, so it is never held
+    /// in memory in its entirety. The returned future resolves to `writer` and the number of bytes
+    /// written once the whole body has been written and `writer` has been flushed.
+    ///
+    /// Note that the body is written regardless of the response status. Errors writing to
+    /// `writer` are reported as `CmdError::Io`.
+    pub fn download_to_writer<W>(
+        self,
+        method: Method,
+        url: &str,
+        writer: W,
+    ) -> impl Future<Item = (W, u64), Error = error::CmdError>
+    where
+        W: tokio::io::AsyncWrite,
+    {
+        self.raw_client_for(method, url)
+            .and_then(move |raw| {
+                raw.into_body().map_err(error::CmdError::from).fold(
+                    (writer, 0),
+                    |(writer, written), chunk| {
+                        let len = chunk.len() as u64;
+                        tokio::io::write_all(writer, chunk)
+                            .map(move |(writer, _)| (writer, written + len))
+                            .map_err(error::CmdError::Io)
+                    },
+                )
+            })
+            .and_then(|(writer, written)| {
+                tokio::io::flush(writer)
+                    .map(move |writer| (writer, written))
+                    .map_err(error::CmdError::Io)
+            })
+    }
+
+    /// Issue an HTTP request to the given `url` with all the same cookies as the current session,
+    /// and get back a `RawResponse` for inspecting the result.
+    ///
+    /// This is a more convenient version of `raw_client_for` for when you do not need to deal with
+    /// `hyper` types directly.
+    pub fn fetch(
+        self,
+        method: Method,
+        url: &str,
+    ) -> impl Future<Item = RawResponse, Error = error::CmdError> {
+        self.raw_client_for(method, url)
+            .map(|inner| RawResponse { inner })
+    }
+
     /// Find an element on the page.
     pub fn find(
         &mut self,
         search: Locator,
     ) -> impl Future<Item = Element, Error = error::CmdError> {
-        by(self.clone(), search.into(), None)
+        if let Err(e) = search.check() {
+            return future::Either::B(future::err(e));
+        }
+        future::Either::A(by(self.clone(), search.into(), None))
     }
 
     /// Find elements on the page.
@@ -784,7 +2542,131 @@ impl Client {
         &mut self,
         search: Locator,
     ) -> impl Future<Item = Vec<Element>, Error = error::CmdError> {
-        find_all(self.clone(), search, None)
+        if let Err(e) = search.check() {
+            return future::Either::B(future::err(e));
+        }
+        future::Either::A(find_all(self.clone(), search.into(), None))
+    }
+
+    /// Find at most `limit` elements on the page, in document order.
+    ///
+    /// On pages with a very large number of matches, this avoids the cost of having the driver
+    /// create a reference for every one of them, as `find_all` does. The lookup is done using
+    /// JavaScript rather than the WebDriver element lookup commands.
+    pub fn find_all_limited(
+        &mut self,
+        search: Locator,
+        limit: usize,
+    ) -> impl Future<Item = Vec<Element>, Error = error::CmdError> {
+        if let Err(e) = search.check() {
+            return future::Either::B(future::err(e));
+        }
+        let locator: webdriver::command::LocatorParameters = search.into();
+        let locator = match serde_json::to_value(&locator) {
+            Ok(locator) => locator,
+            Err(e) => return future::Either::B(future::err(e.into())),
+        };
+
+        let script = format!(
+            "var root = document, using = arguments[0], value = arguments[1];\
+             {}\
+             return found.slice(0, arguments[2]);",
+            FIND_IN_SCRIPT
+        );
+        let args = vec![
+            locator["using"].clone(),
+            locator["value"].clone(),
+            Json::from(limit as u64),
+        ];
+        let c = self.clone();
+        future::Either::A(self.execute(&script, args).and_then(move |res| {
+            let elements = parse_lookup_all(res, c.is_legacy())?;
+            Ok(elements
+                .into_iter()
+                .map(|e| Element {
+                    c: c.clone(),
+                    e: Arc::new(Mutex::new(e)),
+                    origin: None,
+                })
+                .collect())
+        }))
+    }
+
+    /// Check whether there is an element on the page that matches the given locator.
+    ///
+    /// Unlike `find`, this does not fail with `CmdError::NoSuchElement` when there is no match.
+    pub fn element_exists(
+        &mut self,
+        search: Locator,
+    ) -> impl Future<Item = bool, Error = error::CmdError> {
+        self.find_all(search).map(|elements| !elements.is_empty())
+    }
+
+    /// Find an element on the page using a locator prepared with `Locator::compile`.
+    ///
+    /// This is equivalent to `find`, but skips checking and translating the locator every time,
+    /// which is useful when looking for the same element over and over (e.g., when polling).
+    pub fn find_compiled(
+        &mut self,
+        search: &CompiledLocator,
+    ) -> impl Future<Item = Element, Error = error::CmdError> {
+        let c = self.clone();
+        let origin = search.origin();
+        let cmd = ExtensionCommand::FindCompiled(search.body.clone());
+        self.issue(WebDriverCommand::Extension(cmd))
+            .and_then(move |res| {
+                let e = parse_lookup(res, c.is_legacy())?;
+                Ok(Element {
+                    c,
+                    e: Arc::new(Mutex::new(e)),
+                    origin: Some(origin),
+                })
+            })
+    }
+
+    /// Find elements on the page using a locator prepared with `Locator::compile`.
+    ///
+    /// See `find_compiled`.
+    pub fn find_all_compiled(
+        &mut self,
+        search: &CompiledLocator,
+    ) -> impl Future<Item = Vec<Element>, Error = error::CmdError> {
+        let c = self.clone();
+        let cmd = ExtensionCommand::FindAllCompiled(search.body.clone());
+        self.issue(WebDriverCommand::Extension(cmd))
+            .and_then(move |res| {
+                let array = parse_lookup_all(res, c.is_legacy())?;
+                Ok(array
+                    .into_iter()
+                    .map(move |e| Element {
+                        c: c.clone(),
+                        e: Arc::new(Mutex::new(e)),
+                        origin: None,
+                    })
+                    .collect())
+            })
+    }
+
+    /// Set how long `find` and friends keep looking for an element before giving up.
+    ///
+    /// This sets the session's [implicit wait timeout], and leaves its other timeouts alone. The
+    /// timeout applies to all subsequent element lookups, which saves polling with `wait_for_find`
+    /// in many cases. Note that lookups that fail (e.g., when checking that an element is *not*
+    /// present) then take at least this long.
+    ///
+    /// [implicit wait timeout]: https://www.w3.org/TR/webdriver/#dfn-session-implicit-wait-timeout
+    pub fn set_implicit_wait(
+        &mut self,
+        dur: Duration,
+    ) -> impl Future<Item = (), Error = error::CmdError> {
+        let ms = dur.as_secs() * 1000 + u64::from(dur.subsec_millis());
+        match serde_json::from_value(serde_json::json!({ "implicit": ms })) {
+            Ok(params) => future::Either::A(
+                self.issue(WebDriverCommand::SetTimeouts(params))
+                    .map(|_| ()),
+            ),
+            Err(e) => future::Either::B(future::err(error::CmdError::from(e))),
+        }
     }
 
     /// Wait for the given function to return `true` before proceeding.
@@ -793,6 +2675,10 @@ impl Client {
     /// While this currently just spins and yields, it may be more efficient than this in the
     /// future. In particular, in time, it may only run `is_ready` again when an event occurs on
     /// the page.
+    ///
+    /// `is_ready` must not block (e.g., with `std::thread::sleep`) to slow down the checks, as that
+    /// stalls every other future running on the same executor. Use `poll` instead if you want to
+    /// wait between checks.
     pub fn wait_for<F, FF>(
         self,
         mut is_ready: F,
@@ -812,6 +2698,29 @@ impl Client {
         })
     }
 
+    /// Check the given condition every `interval` until it returns `true`.
+    ///
+    /// Unlike sleeping in the closure given to `wait_for`, the wait between checks does not block
+    /// the executor, so other futures keep making progress in the meantime.
+    pub fn poll<F, FF>(
+        &mut self,
+        interval: Duration,
+        mut is_ready: F,
+    ) -> impl Future<Item = (), Error = error::CmdError>
+    where
+        F: FnMut(&mut Client) -> FF,
+        FF: IntoFuture<Item = bool, Error = error::CmdError>,
+    {
+        future::loop_fn(self.clone(), move |mut this| {
+            is_ready(&mut this).into_future().and_then(move |done| {
+                if done {
+                    return future::Either::A(future::ok(future::Loop::Break(())));
+                }
+                future::Either::B(sleep(interval).map(move |_| future::Loop::Continue(this)))
+            })
+        })
+    }
+
     /// Wait for the given element to be present on the page.
     ///
     /// This can be useful to wait for something to appear on the page before interacting with it.
@@ -822,8 +2731,11 @@ impl Client {
         self,
         search: Locator,
     ) -> impl Future<Item = Element, Error = error::CmdError> {
+        if let Err(e) = search.check() {
+            return future::Either::B(future::err(e));
+        }
         let s: webdriver::command::LocatorParameters = search.into();
-        futures::future::loop_fn(self, move |this| {
+        let wait = futures::future::loop_fn(self, move |this| {
             by(this.clone(), webdriver::command::LocatorParameters {
                 using: s.using.clone(),
                 value: s.value.clone(),
@@ -836,7 +2748,87 @@ impl Client {
                     Err(e)
                 }
             })
-        })
+        });
+        future::Either::A(wait)
+    }
+
+    /// Wait for an element matching the given locator to be present, displayed, and enabled, so
+    /// that it can be clicked.
+    ///
+    /// If no such element appears within `timeout`, `CmdError::WaitTimeout` is returned.
+    pub fn wait_for_clickable(
+        self,
+        search: Locator,
+        timeout: Duration,
+    ) -> impl Future<Item = Element, Error = error::CmdError> {
+        if let Err(e) = search.check() {
+            return future::Either::B(future::err(e));
+        }
+        let s: webdriver::command::LocatorParameters = search.into();
+        let wait = future::loop_fn(self, move |this| {
+            let locator = webdriver::command::LocatorParameters {
+                using: s.using.clone(),
+                value: s.value.clone(),
+            };
+            by(this.clone(), locator, None)
+                .and_then(|e| e.displayed().map(move |displayed| (e, displayed)))
+                .and_then(|(mut e, displayed)| {
+                    if displayed {
+                        future::Either::A(e.is_enabled().map(move |enabled| (e, enabled)))
+                    } else {
+                        future::Either::B(future::ok((e, false)))
+                    }
+                })
+                .then(move |r| match r {
+                    Ok((e, true)) => future::Either::A(future::ok(future::Loop::Break(e))),
+                    // the element may not be there yet, or may have been replaced while we looked
+                    Ok((_, false))
+                    | Err(error::CmdError::NoSuchElement(_))
+                    | Err(error::CmdError::Standard(WebDriverError {
+                        error: webdriver::error::ErrorStatus::StaleElementReference,
+                        ..
+                    })) => future::Either::B(
+                        sleep(POLL_INTERVAL).map(move |_| future::Loop::Continue(this)),
+                    ),
+                    Err(e) => future::Either::A(future::err(e)),
+                })
+        });
+        future::Either::A(wait_with_timeout(wait, timeout))
+    }
+
+    /// Get the text of the currently open user prompt, such as an `alert` or `confirm` dialog.
+    ///
+    /// If no prompt is open, this fails with a `CmdError::Standard` error whose status is
+    /// `ErrorStatus::NoSuchAlert`.
+    pub fn get_alert_text(&mut self) -> impl Future<Item = String, Error = error::CmdError> {
+        self.issue(WebDriverCommand::GetAlertText)
+            .and_then(|v| match v {
+                Json::String(v) => Ok(v),
+                v => Err(error::CmdError::NotW3C(v)),
+            })
+    }
+
+    /// Wait for a user prompt, such as an `alert`, to open, and get its text.
+    ///
+    /// If no prompt opens within `timeout`, `CmdError::WaitTimeout` is returned. The prompt is
+    /// left open.
+    pub fn wait_for_alert(
+        &mut self,
+        timeout: Duration,
+    ) -> impl Future<Item = String, Error = error::CmdError> {
+        let wait = future::loop_fn(self.clone(), |mut this| {
+            this.get_alert_text().then(move |r| match r {
+                Ok(text) => future::Either::A(future::ok(future::Loop::Break(text))),
+                Err(error::CmdError::Standard(WebDriverError {
+                    error: webdriver::error::ErrorStatus::NoSuchAlert,
+                    ..
+                })) => future::Either::B(
+                    sleep(POLL_INTERVAL).map(move |_| future::Loop::Continue(this)),
+                ),
+                Err(e) => future::Either::A(future::err(e)),
+            })
+        });
+        wait_with_timeout(wait, timeout)
     }
 
     /// Wait for the page to navigate to a new URL before proceeding.
@@ -853,51 +2845,523 @@ impl Client {
             None => future::Either::B(self.current_url_()),
         }
         .and_then(move |current| {
-            self.wait_for(move |c| {
+            future::loop_fn(self, move |mut this| {
                 // TODO: get rid of this clone
                 let current = current.clone();
-                c.current_url().map(move |url| url != current)
+                this.current_url().and_then(move |url| {
+                    if url != current {
+                        return future::Either::A(future::ok(future::Loop::Break(this)));
+                    }
+                    future::Either::B(
+                        sleep(POLL_INTERVAL).map(move |_| future::Loop::Continue(this)),
+                    )
+                })
             })
         })
     }
 
+    /// Wait for the page to navigate to a new URL before proceeding, for at most `timeout`.
+    ///
+    /// This behaves like `wait_for_navigation`, except that `CmdError::WaitTimeout` is returned
+    /// if the page has not navigated away from `current` within `timeout`.
+    pub fn wait_for_navigation_timeout(
+        self,
+        current: Option<url::Url>,
+        timeout: Duration,
+    ) -> impl Future<Item = Self, Error = error::CmdError> {
+        wait_with_timeout(self.wait_for_navigation(current), timeout)
+    }
+
+    /// Wait for the title of the current page to satisfy `predicate` before proceeding.
+    ///
+    /// This is useful after navigation that happens asynchronously, such as in single-page
+    /// applications. If the title does not match within `timeout`, `CmdError::WaitTimeout` is
+    /// returned.
+    pub fn wait_for_title<F>(
+        self,
+        predicate: F,
+        timeout: Duration,
+    ) -> impl Future<Item = Self, Error = error::CmdError>
+    where
+        F: Fn(&str) -> bool,
+    {
+        let wait = future::loop_fn((self, predicate), |(mut this, predicate)| {
+            this.title().and_then(move |title| {
+                if predicate(&title) {
+                    return future::Either::A(future::ok(future::Loop::Break(this)));
+                }
+                future::Either::B(
+                    sleep(POLL_INTERVAL).map(move |_| future::Loop::Continue((this, predicate))),
+                )
+            })
+        });
+        wait_with_timeout(wait, timeout)
+    }
+
+    /// Wait for the current URL to change, and return the new URL.
+    ///
+    /// The current URL is recorded when this is called, so call this before triggering the
+    /// change. Unlike `wait_for_navigation`, the new URL does not need to be known up front. If the
+    /// URL does not change within `timeout`, `CmdError::WaitTimeout` is returned.
+    pub fn wait_for_url_change(
+        &mut self,
+        timeout: Duration,
+    ) -> impl Future<Item = url::Url, Error = error::CmdError> {
+        let this = self.clone();
+        let wait = self.current_url_().and_then(move |start| {
+            future::loop_fn(this, move |mut this| {
+                let start = start.clone();
+                this.current_url_().and_then(move |url| {
+                    if url != start {
+                        return future::Either::A(future::ok(future::Loop::Break(url)));
+                    }
+                    future::Either::B(
+                        sleep(POLL_INTERVAL).map(move |_| future::Loop::Continue(this)),
+                    )
+                })
+            })
+        });
+        wait_with_timeout(wait, timeout)
+    }
+
     /// Locate a form on the page.
     ///
     /// Through the returned `Form`, HTML forms can be filled out and submitted.
     pub fn form(&mut self, search: Locator) -> impl Future<Item = Form, Error = error::CmdError> {
+        if let Err(e) = search.check() {
+            return future::Either::B(future::err(e));
+        }
         let mut c = self.clone();
-        c.issue(WebDriverCommand::FindElement(search.into()))
+        let form = c
+            .issue(WebDriverCommand::FindElement(search.into()))
             .and_then(move |res| {
                 let f = parse_lookup(res, c.is_legacy());
                 f.map(move |f| Form { c: c.clone(), f: f })
-            })
+            });
+        future::Either::A(form)
     }
 
-    // helpers
-    fn fixup_elements(&self, args: &mut [Json]) {
-        if self.is_legacy() {
-            for arg in args {
-                // the serialization of WebElement uses the W3C index,
-                // but legacy implementations need us to use the "ELEMENT" index
-                if let Json::Object(ref mut o) = *arg {
-                    if let Some(wei) = o.remove(ELEMENT_KEY) {
-                        o.insert("ELEMENT".to_string(), wei);
-                    }
-                }
-            }
-        }
-    }
+    /// Set the state of the permission with the given `name` (e.g., `geolocation` or
+    /// `notifications`), so that permission-gated features can be used without a prompt.
+    ///
+    /// On Chromium, this uses the DevTools `Browser.setPermission` command, and applies to all
+    /// origins. `Browser.grantPermissions` is not used since it can only grant permissions, not
+    /// deny them or reset them to prompt the user. Elsewhere, it uses the [Set Permission] WebDriver extension command, which applies
+    /// to the current origin, and which not all drivers support.
+    ///
+    /// [Set Permission]: https://w3c.github.io/permissions/#webdriver-command-set-permission
+    pub fn set_permission(
+        &mut self,
+        name: &str,
+        state: PermissionState,
+    ) -> impl Future<Item = (), Error = error::CmdError> {
+        if self.is_chromium() {
+            let params = serde_json::json!({
+                "permission": { "name": name },
+                "setting": state.as_str(),
+            });
+            future::Either::A(self.cdp("Browser.setPermission", params).map(|_| ()))
+        } else {
+            let params = serde_json::json!({
+                "descriptor": { "name": name },
+                "state": state.as_str(),
+            });
+            let cmd = WebDriverCommand::Extension(ExtensionCommand::SetPermission(params));
+            future::Either::B(self.issue(cmd).map(|_| ()))
+        }
+    }
+
+    /// Clear the browser's HTTP cache, so that the next page loads are served from the network.
+    ///
+    /// This is only supported on Chromium, where it uses the DevTools `Network.clearBrowserCache`
+    /// command. Other browsers fail with a `CmdError::Standard` error whose status is
+    /// `ErrorStatus::UnsupportedOperation`; on Firefox, you can instead start the session with
+    /// the `browser.cache.disk.enable` and `browser.cache.memory.enable` preferences set to
+    /// `false`.
+    pub fn clear_cache(&mut self) -> impl Future<Item = (), Error = error::CmdError> {
+        if !self.is_chromium() {
+            let e = WebDriverError::new(
+                webdriver::error::ErrorStatus::UnsupportedOperation,
+                "the HTTP cache can only be cleared on Chromium; \
+                 disable the cache through the browser's preferences instead",
+            );
+            return future::Either::B(future::err(error::CmdError::Standard(e)));
+        }
+
+        let params = serde_json::json!({});
+        future::Either::A(self.cdp("Network.clearBrowserCache", params).map(|_| ()))
+    }
+
+    /// Make the browser send the given HTTP headers along with every request it makes.
+    ///
+    /// This is useful to, for example, authenticate against a staging server. Each call replaces
+    /// the headers given previously, so an empty map stops sending extra headers again. Note that
+    /// unlike `ClientBuilder::header`, this affects the requests the browser itself makes, and not
+    /// the control channel to the WebDriver server.
+    ///
+    /// This is only supported on Chromium, where it uses the DevTools
+    /// `Network.setExtraHTTPHeaders` command. Other browsers fail with a `CmdError::Standard`
+    /// error whose status is `ErrorStatus::UnsupportedOperation`.
+    pub fn set_request_headers(
+        &mut self,
+        headers: std::collections::HashMap<String, String>,
+    ) -> impl Future<Item = (), Error = error::CmdError> {
+        if !self.is_chromium() {
+            let e = WebDriverError::new(
+                webdriver::error::ErrorStatus::UnsupportedOperation,
+                "extra request headers can only be set on Chromium",
+            );
+            return future::Either::B(future::err(error::CmdError::Standard(e)));
+        }
+
+        // the headers are only sent once the network domain is enabled
+        let params = serde_json::json!({ "headers": headers });
+        let mut this = self.clone();
+        future::Either::A(
+            self.cdp("Network.enable", serde_json::json!({}))
+                .and_then(move |_| this.cdp("Network.setExtraHTTPHeaders", params))
+                .map(|_| ()),
+        )
+    }
+
+    /// Make the browser fail all requests to URLs that match any of the given `patterns`.
+    ///
+    /// Patterns may use `*` as a wildcard, such as `*.png` or `*://ads.example.com/*`. Each call
+    /// replaces the patterns given previously, so an empty list unblocks all URLs again. This is
+    /// useful to check how a page copes when third-party resources fail to load.
+    ///
+    /// This is only supported on Chromium, where it uses the DevTools `Network.setBlockedURLs`
+    /// command. Other browsers fail with a `CmdError::Standard` error whose status is
+    /// `ErrorStatus::UnsupportedOperation`.
+    pub fn set_blocked_urls(
+        &mut self,
+        patterns: &[&str],
+    ) -> impl Future<Item = (), Error = error::CmdError> {
+        if !self.is_chromium() {
+            let e = WebDriverError::new(
+                webdriver::error::ErrorStatus::UnsupportedOperation,
+                "URLs can only be blocked on Chromium",
+            );
+            return future::Either::B(future::err(error::CmdError::Standard(e)));
+        }
+
+        // blocking only takes effect once the network domain is enabled
+        let params = serde_json::json!({ "urls": patterns });
+        let mut this = self.clone();
+        future::Either::A(
+            self.cdp("Network.enable", serde_json::json!({}))
+                .and_then(move |_| this.cdp("Network.setBlockedURLs", params))
+                .map(|_| ()),
+        )
+    }
+
+    /// Make the browser save downloaded files to the given directory, without prompting.
+    ///
+    /// This is only supported on Chromium, where it uses the DevTools `Page.setDownloadBehavior`
+    /// command. Other browsers fail with a `CmdError::Standard` error whose status is
+    /// `ErrorStatus::UnsupportedOperation`; use `ClientBuilder::download_directory` to set the
+    /// download directory when the session is created instead. Relative paths are resolved
+    /// against the current working directory.
+    pub fn set_download_directory(
+        &mut self,
+        dir: &std::path::Path,
+    ) -> impl Future<Item = (), Error = error::CmdError> {
+        if !self.is_chromium() {
+            let e = WebDriverError::new(
+                webdriver::error::ErrorStatus::UnsupportedOperation,
+                "the download directory can only be changed on Chromium; \
+                 use ClientBuilder::download_directory instead",
+            );
+            return future::Either::B(future::err(error::CmdError::Standard(e)));
+        }
+
+        let dir = match absolute_path(dir, "dir") {
+            Ok(dir) => dir,
+            Err(e) => return future::Either::B(future::err(e)),
+        };
+        let params = serde_json::json!({ "behavior": "allow", "downloadPath": dir });
+        future::Either::A(self.cdp("Page.setDownloadBehavior", params).map(|_| ()))
+    }
+
+    /// Wait for a download to complete in the given directory, and return the path of the
+    /// downloaded file.
+    ///
+    /// Files that are already in the directory when this is called are ignored, so call this
+    /// before triggering the download. Files that browsers use while a download is still in
+    /// progress (`.crdownload` and `.part` files) are also ignored. If no download completes
+    /// within `timeout`, `CmdError::WaitTimeout` is returned.
+    pub fn wait_for_download(
+        &mut self,
+        dir: &std::path::Path,
+        timeout: Duration,
+    ) -> impl Future<Item = std::path::PathBuf, Error = error::CmdError> {
+        fn is_partial(name: &std::ffi::OsStr) -> bool {
+            let name = name.to_string_lossy();
+            name.ends_with(".crdownload") || name.ends_with(".part")
+        }
+
+        fn list(dir: &std::path::Path) -> io::Result<Vec<std::ffi::OsString>> {
+            std::fs::read_dir(dir)?
+                .map(|entry| entry.map(|entry| entry.file_name()))
+                .collect()
+        }
+
+        let dir = dir.to_path_buf();
+        let existing: std::collections::HashSet<_> = match list(&dir) {
+            Ok(files) => files.into_iter().collect(),
+            Err(e) => return future::Either::B(future::err(error::CmdError::Io(e))),
+        };
+
+        let wait = future::loop_fn((dir, existing), |(dir, existing)| {
+            sleep(POLL_INTERVAL).and_then(move |_| {
+                let files = list(&dir).map_err(error::CmdError::Io)?;
+                let done = files.iter().find(|&name| {
+                    if existing.contains(name) || is_partial(name) {
+                        return false;
+                    }
+
+                    // Firefox creates the file up front, and fills it in from a .part file
+                    !files.iter().any(|other| {
+                        is_partial(other)
+                            && other
+                                .to_string_lossy()
+                                .starts_with(&*name.to_string_lossy())
+                    })
+                });
+                Ok(match done {
+                    Some(name) => future::Loop::Break(dir.join(name)),
+                    None => future::Loop::Continue((dir, existing)),
+                })
+            })
+        });
+        future::Either::A(wait_with_timeout(wait, timeout))
+    }
+
+    /// Perform the given chain of low-level input actions.
+    ///
+    /// See <https://www.w3.org/TR/webdriver/#perform-actions>.
+    pub fn perform_actions(
+        &mut self,
+        mut actions: ActionChain,
+    ) -> impl Future<Item = (), Error = error::CmdError> {
+        // moves relative to an element's corner need to know how large the element is
+        let origins = actions.corner_origins();
+        let sizes = if origins.is_empty() {
+            future::Either::A(future::ok(Vec::new()))
+        } else {
+            let script = "\
+                          return arguments[0].map(function(e) {\
+                            var r = e.getBoundingClientRect();\
+                            return [r.width, r.height];\
+                          })";
+            let n = origins.len();
+            future::Either::B(self.execute(script, vec![Json::Array(origins)]).and_then(
+                move |res| {
+                    let sizes = res.as_array().and_then(|sizes| {
+                        sizes
+                            .iter()
+                            .map(|s| Some((s[0].as_f64()?, s[1].as_f64()?)))
+                            .collect::<Option<Vec<_>>>()
+                    });
+                    match sizes {
+                        Some(ref sizes) if sizes.len() == n => Ok(sizes.clone()),
+                        _ => Err(error::CmdError::NotW3C(res)),
+                    }
+                },
+            ))
+        };
+
+        let mut this = self.clone();
+        sizes.and_then(move |sizes| {
+            actions.resolve_corner_offsets(&sizes);
+            match serde_json::from_value(actions.into_json()) {
+                Ok(params) => future::Either::A(
+                    this.issue(WebDriverCommand::PerformActions(params))
+                        .map(|_| ()),
+                ),
+                Err(e) => future::Either::B(future::err(error::CmdError::from(e))),
+            }
+        })
+    }
+
+    // helpers
+
+    /// Set an item in the given [web storage] area (`localStorage` or `sessionStorage`).
+    ///
+    /// [web storage]: https://html.spec.whatwg.org/multipage/webstorage.html
+    fn set_storage_item(
+        &mut self,
+        storage: &str,
+        key: &str,
+        value: &str,
+    ) -> impl Future<Item = (), Error = error::CmdError> {
+        let script = format!("window.{}.setItem(arguments[0], arguments[1]);", storage);
+        self.execute(&script, vec![Json::from(key), Json::from(value)])
+            .map(|_| ())
+    }
+
+    /// Get an item from the given web storage area.
+    fn get_storage_item(
+        &mut self,
+        storage: &str,
+        key: &str,
+    ) -> impl Future<Item = Option<String>, Error = error::CmdError> {
+        let script = format!("return window.{}.getItem(arguments[0]);", storage);
+        self.execute(&script, vec![Json::from(key)])
+            .and_then(|v| match v {
+                Json::String(v) => Ok(Some(v)),
+                Json::Null => Ok(None),
+                v => Err(error::CmdError::NotW3C(v)),
+            })
+    }
+
+    /// Remove all items from the given web storage area.
+    fn clear_storage(&mut self, storage: &str) -> impl Future<Item = (), Error = error::CmdError> {
+        self.execute(&format!("window.{}.clear();", storage), vec![])
+            .map(|_| ())
+    }
+
+    /// Run a Chrome DevTools Protocol command, and return its result.
+    ///
+    /// This is only supported by chromedriver.
+    fn cdp(&mut self, cmd: &str, params: Json) -> impl Future<Item = Json, Error = error::CmdError> {
+        self.issue(WebDriverCommand::Extension(ExtensionCommand::ChromeCdp {
+            cmd: cmd.to_string(),
+            params,
+        }))
+    }
+
+    /// Take a screenshot of the rectangle `[x, y, width, height]`, in document coordinates,
+    /// returned by running `script` with the given arguments, using the DevTools protocol.
+    ///
+    /// If `script` returns `null`, the rectangle is taken to be outside the viewport.
+    fn clipped_screenshot(
+        &mut self,
+        script: &str,
+        args: Vec<Json>,
+        beyond_viewport: bool,
+    ) -> impl Future<Item = Vec<u8>, Error = error::CmdError> {
+        let mut c = self.clone();
+        self.execute(script, args)
+            .and_then(|rect| {
+                if rect.is_null() {
+                    let e = WebDriverError::new(
+                        webdriver::error::ErrorStatus::UnableToCaptureScreen,
+                        "the region to capture is not in the viewport",
+                    );
+                    return Err(error::CmdError::Standard(e));
+                }
+                let clip = rect.as_array().and_then(|r| {
+                    if r.len() == 4 && r.iter().all(|v| v.is_number()) {
+                        Some(serde_json::json!({
+                            "x": r[0],
+                            "y": r[1],
+                            "width": r[2],
+                            "height": r[3],
+                            "scale": 1,
+                        }))
+                    } else {
+                        None
+                    }
+                });
+                match clip {
+                    Some(clip) => Ok(clip),
+                    None => Err(error::CmdError::NotW3C(rect)),
+                }
+            })
+            .and_then(move |clip| {
+                let params = serde_json::json!({
+                    "format": "png",
+                    "clip": clip,
+                    "captureBeyondViewport": beyond_viewport,
+                });
+                c.cdp("Page.captureScreenshot", params)
+            })
+            .and_then(|res| {
+                if let Some(data) = res.get("data").and_then(|data| data.as_str()) {
+                    return base64::decode(data).map_err(|e| error::CmdError::ImageDecodeError(e));
+                }
+
+                Err(error::CmdError::NotW3C(res))
+            })
+    }
+
+    /// The `(host, port)` of the HTTP proxy that a raw request for `url` should go through, if any.
+    fn raw_proxy(&self, url: &url::Url) -> Result<Option<(String, u16)>, error::CmdError> {
+        if !self.proxies_raw_requests() {
+            return Ok(None);
+        }
+
+        // not all drivers report the proxy they were given, so also check what we asked for
+        let caps = self.capabilities();
+        let proxy = caps
+            .as_ref()
+            .map(|caps| &**caps)
+            .into_iter()
+            .chain(self.requested_capabilities())
+            .filter_map(|caps| caps.get("proxy"))
+            .filter(|proxy| proxy["proxyType"] == "manual")
+            .filter_map(|proxy| proxy["httpProxy"].as_str())
+            .next();
+        let proxy = match proxy {
+            Some(proxy) => proxy,
+            None => return Ok(None),
+        };
+
+        if url.scheme() != "http" {
+            return Err(error::CmdError::InvalidArgument(
+                "url".to_string(),
+                format!("{} cannot be sent through the session's HTTP proxy", url),
+            ));
+        }
+        let proxy = url::Url::parse(&format!("http://{}", proxy))?;
+        match (proxy.host_str(), proxy.port_or_known_default()) {
+            (Some(host), Some(port)) => Ok(Some((host.to_string(), port))),
+            _ => Err(error::CmdError::BadUrl(url::ParseError::EmptyHost)),
+        }
+    }
+
+    fn fixup_elements(&self, args: &mut [Json]) {
+        if self.is_legacy() {
+            for arg in args {
+                // the serialization of WebElement uses the W3C index,
+                // but legacy implementations need us to use the "ELEMENT" index
+                if let Json::Object(ref mut o) = *arg {
+                    if let Some(wei) = o.remove(ELEMENT_KEY) {
+                        o.insert("ELEMENT".to_string(), wei);
+                    }
+                }
+            }
+        }
+    }
 }
 
+/// JavaScript that finds the elements below `root` that match the locator given by `using` and
+/// `value` (as in `LocatorParameters`), and leaves them in the array `found`.
+const FIND_IN_SCRIPT: &str = "\
+                              var found = [];\
+                              if (using === 'xpath') {\
+                                var r = document.evaluate(value, root, null,\
+                                  XPathResult.ORDERED_NODE_SNAPSHOT_TYPE, null);\
+                                for (var i = 0; i < r.snapshotLength; i++) {\
+                                  found.push(r.snapshotItem(i));\
+                                }\
+                              } else if (using === 'link text') {\
+                                found = Array.prototype.filter.call(root.querySelectorAll('a'),\
+                                  function(a) { return a.innerText.trim() === value; });\
+                              } else {\
+                                found = Array.prototype.slice.call(root.querySelectorAll(value));\
+                              }";
+
 /// Find elements on the page.
 fn find_all(
     c: Client,
-    search: Locator,
+    locator: webdriver::command::LocatorParameters,
     parent_element: Option<&Element>,
 ) -> impl Future<Item = Vec<Element>, Error = error::CmdError> {
     let command = match parent_element {
-        None => WebDriverCommand::FindElements(search.into()),
-        Some(el) => WebDriverCommand::FindElementElements(el.e.clone(), search.into()),
+        None => WebDriverCommand::FindElements(locator),
+        Some(el) => WebDriverCommand::FindElementElements(el.web_element(), locator),
     };
 
     c.clone().issue(command)
@@ -907,27 +3371,209 @@ fn find_all(
                 .into_iter()
                 .map(move |e| Element {
                     c: c.clone(),
-                    e: e,
+                    e: Arc::new(Mutex::new(e)),
+                    origin: None,
                 })
-                .collect())
+                .collect::<Vec<_>>())
         })
 }
 
+/// Convert a [serialized cookie] returned by the WebDriver server into a `Cookie`.
+///
+/// [serialized cookie]: https://www.w3.org/TR/webdriver/#dfn-serialized-cookie
+fn parse_cookie(cookie: Json) -> Result<Cookie<'static>, error::CmdError> {
+    let name = cookie["name"].as_str().map(String::from);
+    let value = cookie["value"].as_str().map(String::from);
+    let mut builder = match (name, value) {
+        (Some(name), Some(value)) => Cookie::build(name, value),
+        _ => return Err(error::CmdError::NotW3C(cookie)),
+    };
+
+    if let Some(domain) = cookie["domain"].as_str() {
+        builder = builder.domain(domain.to_string());
+    }
+    if let Some(path) = cookie["path"].as_str() {
+        builder = builder.path(path.to_string());
+    }
+    if let Some(secure) = cookie["secure"].as_bool() {
+        builder = builder.secure(secure);
+    }
+    if let Some(http_only) = cookie["httpOnly"].as_bool() {
+        builder = builder.http_only(http_only);
+    }
+    if let Some(expiry) = cookie["expiry"].as_i64() {
+        builder = builder.expires(time::at_utc(time::Timespec::new(expiry, 0)));
+    }
+    Ok(builder.finish())
+}
+
+/// Extract the x, y, width, and height of a window from the response to a `GetWindowRect` or
+/// `MinimizeWindow` command.
+fn parse_window_rect(v: Json) -> Result<(u64, u64, u64, u64), error::CmdError> {
+    match v {
+        Json::Object(mut obj) => {
+            let x = match obj.remove("x").and_then(|x| x.as_u64()) {
+                Some(x) => x,
+                None => return Err(error::CmdError::NotW3C(Json::Object(obj))),
+            };
+
+            let y = match obj.remove("y").and_then(|y| y.as_u64()) {
+                Some(y) => y,
+                None => return Err(error::CmdError::NotW3C(Json::Object(obj))),
+            };
+
+            let width = match obj.remove("width").and_then(|width| width.as_u64()) {
+                Some(width) => width,
+                None => return Err(error::CmdError::NotW3C(Json::Object(obj))),
+            };
+
+            let height = match obj.remove("height").and_then(|height| height.as_u64()) {
+                Some(height) => height,
+                None => return Err(error::CmdError::NotW3C(Json::Object(obj))),
+            };
+
+            Ok((x, y, width, height))
+        }
+        _ => Err(error::CmdError::NotW3C(v)),
+    }
+}
+
+/// Extract a `WindowHandle` from the response to a `GetWindowHandle` command.
+fn parse_window_handle(res: Json) -> Result<WindowHandle, error::CmdError> {
+    match res {
+        Json::String(handle) => Ok(WindowHandle(handle)),
+        v => Err(error::CmdError::NotW3C(v)),
+    }
+}
+
+/// Extract `WindowHandle`s from the response to a `GetWindowHandles` or `CloseWindow` command.
+fn parse_window_handles(res: Json) -> Result<Vec<WindowHandle>, error::CmdError> {
+    match res {
+        Json::Array(handles) => handles.into_iter().map(parse_window_handle).collect(),
+        v => Err(error::CmdError::NotW3C(v)),
+    }
+}
+
+/// How long to wait between checks when polling the browser for some condition.
+const POLL_INTERVAL: Duration = Duration::from_millis(100);
+
+/// Wait for `duration` without blocking the executor.
+fn sleep(duration: Duration) -> impl Future<Item = (), Error = error::CmdError> {
+    tokio::timer::Delay::new(Instant::now() + duration)
+        .map_err(|e| error::CmdError::Io(io::Error::new(io::ErrorKind::Other, e)))
+}
+
+/// Write `contents` to the file at `path`, replacing it if it already exists.
+///
+/// The file is written on a thread of its own, so that a slow disk does not stall the executor.
+fn write_file(
+    path: std::path::PathBuf,
+    contents: Vec<u8>,
+) -> impl Future<Item = (), Error = error::CmdError> {
+    let (tx, rx) = futures::sync::oneshot::channel();
+    std::thread::spawn(move || {
+        let _ = tx.send(std::fs::write(&path, contents));
+    });
+    rx.then(|r| match r {
+        Ok(written) => written.map_err(error::CmdError::Io),
+        Err(_) => Err(error::CmdError::Io(io::Error::new(
+            io::ErrorKind::Other,
+            "the thread writing the file went away",
+        ))),
+    })
+}
+
+/// Bound the given wait by `timeout`, reporting expiry as `CmdError::WaitTimeout`.
+fn wait_with_timeout<F>(
+    wait: F,
+    timeout: Duration,
+) -> impl Future<Item = F::Item, Error = error::CmdError>
+where
+    F: Future<Error = error::CmdError>,
+{
+    limit(wait, timeout, error::CmdError::WaitTimeout)
+}
+
+/// Fail the given command with `CmdError::Timeout` if it does not complete within `deadline`.
+///
+/// This is useful to guard against commands that hang, for example due to driver bugs, even when
+/// the session's own timeouts are set. If the deadline expires, the request to the WebDriver
+/// server is abandoned. Note that the browser may still be busy carrying out the command, which
+/// may delay subsequent commands.
+///
+/// ```no_run
+/// # extern crate fantoccini;
+/// # extern crate tokio;
+/// # use fantoccini::Client;
+/// # use std::time::Duration;
+/// # use tokio::prelude::*;
+/// # fn demo(mut c: Client) -> impl Future<Item = (), Error = fantoccini::error::CmdError> {
+/// fantoccini::with_deadline(c.execute("return 42", vec![]), Duration::from_secs(5)).map(|_| ())
+/// # }
+/// # fn main() {}
+/// ```
+pub fn with_deadline<F>(
+    command: F,
+    deadline: Duration,
+) -> impl Future<Item = F::Item, Error = error::CmdError>
+where
+    F: Future<Error = error::CmdError>,
+{
+    limit(command, deadline, error::CmdError::Timeout)
+}
+
+/// Resolve `path` against the current working directory, for sending to the WebDriver server.
+///
+/// `arg` names the argument the path was given in, for error reporting.
+fn absolute_path(path: &std::path::Path, arg: &str) -> Result<String, error::CmdError> {
+    let path = std::env::current_dir().map_err(error::CmdError::Io)?.join(path);
+    match path.to_str() {
+        Some(path) => Ok(path.to_string()),
+        None => Err(error::CmdError::InvalidArgument(
+            arg.to_string(),
+            format!("{} is not valid UTF-8", path.display()),
+        )),
+    }
+}
+
+/// Bound `f` by `timeout`, reporting expiry as `elapsed`.
+fn limit<F>(
+    f: F,
+    timeout: Duration,
+    elapsed: error::CmdError,
+) -> impl Future<Item = F::Item, Error = error::CmdError>
+where
+    F: Future<Error = error::CmdError>,
+{
+    tokio::timer::Timeout::new(f, timeout).map_err(move |e| {
+        if e.is_elapsed() {
+            elapsed
+        } else if e.is_timer() {
+            let e = e.into_timer().unwrap();
+            error::CmdError::Io(io::Error::new(io::ErrorKind::Other, e))
+        } else {
+            e.into_inner().unwrap()
+        }
+    })
+}
+
 fn by(
-    mut c: Client,
+    c: Client,
     locator: webdriver::command::LocatorParameters,
     parent_element: Option<&Element>,
 ) -> impl Future<Item = Element, Error = error::CmdError> {
-    let command = match parent_element {
-        None => WebDriverCommand::FindElement(locator),
-        Some(el) => WebDriverCommand::FindElementElement(el.e.clone(), locator),
+    // remember how we found the element, in case we need to find it again
+    let origin = Origin {
+        using: locator.using,
+        value: locator.value,
+        parent: parent_element.map(|el| el.web_element()),
     };
 
-    c.issue(command)
-        .and_then(move |res| {
-            let e = parse_lookup(res, c.is_legacy());
-            e.map(move |e| Element { c: c.clone(), e: e })
-        })
+    origin.find(c.clone()).map(move |e| Element {
+        c,
+        e: Arc::new(Mutex::new(e)),
+        origin: Some(origin),
+    })
 }
 
 /// Extract the `WebElement` from a `FindElement` or `FindElementElement` command.
@@ -990,12 +3636,68 @@ impl Element {
         &mut self,
         attribute: &str,
     ) -> impl Future<Item = Option<String>, Error = error::CmdError> {
-        let cmd = WebDriverCommand::GetElementAttribute(self.e.clone(), attribute.to_string());
-        self.c.issue(cmd).and_then(|v| match v {
-            Json::String(v) => Ok(Some(v)),
-            Json::Null => Ok(None),
-            v => Err(error::CmdError::NotW3C(v)),
-        })
+        let attribute = attribute.to_string();
+        self.issue(move |e| WebDriverCommand::GetElementAttribute(e, attribute.clone()))
+            .and_then(|v| match v {
+                Json::String(v) => Ok(Some(v)),
+                Json::Null => Ok(None),
+                v => Err(error::CmdError::NotW3C(v)),
+            })
+    }
+
+    /// Check whether this element and `other` refer to the same node on the page.
+    ///
+    /// Element references cannot be compared directly, since a driver may hand out different
+    /// references for the same node, so this compares the nodes themselves using JavaScript.
+    pub fn equals(&mut self, other: &Element) -> impl Future<Item = bool, Error = error::CmdError> {
+        let args = vec![
+            via_json!(&self.web_element()),
+            via_json!(&other.web_element()),
+        ];
+        self.c
+            .execute("return arguments[0] === arguments[1];", args)
+            .and_then(|v| match v {
+                Json::Bool(v) => Ok(v),
+                v => Err(error::CmdError::NotW3C(v)),
+            })
+    }
+
+    /// Check whether this element has the [attribute] with the given name, whatever its value.
+    ///
+    /// This is mostly useful for boolean attributes such as `disabled` or `checked`, for which
+    /// drivers disagree on what `attr` returns. Attribute names are matched case-insensitively
+    /// for HTML elements.
+    ///
+    /// [attribute]: https://dom.spec.whatwg.org/#concept-attribute
+    pub fn has_attr(&mut self, name: &str) -> impl Future<Item = bool, Error = error::CmdError> {
+        let args = vec![via_json!(&self.web_element()), Json::from(name)];
+        self.c
+            .execute("return arguments[0].hasAttribute(arguments[1]);", args)
+            .and_then(|v| match v {
+                Json::Bool(v) => Ok(v),
+                v => Err(error::CmdError::NotW3C(v)),
+            })
+    }
+
+    /// Set the [attribute] with the given name on this element to `value`.
+    ///
+    /// WebDriver has no command for this, so it is done using JavaScript. This is mostly useful
+    /// for setting up test fixtures.
+    ///
+    /// [attribute]: https://dom.spec.whatwg.org/#concept-attribute
+    pub fn set_attribute(
+        &mut self,
+        name: &str,
+        value: &str,
+    ) -> impl Future<Item = (), Error = error::CmdError> {
+        let args = vec![
+            via_json!(&self.web_element()),
+            Json::from(name),
+            Json::from(value),
+        ];
+        self.c
+            .execute("arguments[0].setAttribute(arguments[1], arguments[2]);", args)
+            .map(|_| ())
     }
 
     /// Look up a DOM [property] for this element by name.
@@ -1007,23 +3709,70 @@ impl Element {
         &mut self,
         prop: &str,
     ) -> impl Future<Item = Option<String>, Error = error::CmdError> {
-        let cmd = WebDriverCommand::GetElementProperty(self.e.clone(), prop.to_string());
-        self.c.issue(cmd).and_then(|v| match v {
-            Json::String(v) => Ok(Some(v)),
-            Json::Null => Ok(None),
-            v => Err(error::CmdError::NotW3C(v)),
-        })
+        let prop = prop.to_string();
+        self.issue(move |e| WebDriverCommand::GetElementProperty(e, prop.clone()))
+            .and_then(|v| match v {
+                Json::String(v) => Ok(Some(v)),
+                Json::Null => Ok(None),
+                v => Err(error::CmdError::NotW3C(v)),
+            })
     }
 
     /// Retrieve the text contents of this elment.
     pub fn text(&mut self) -> impl Future<Item = String, Error = error::CmdError> {
-        let cmd = WebDriverCommand::GetElementText(self.e.clone());
-        self.c.issue(cmd).and_then(|v| match v {
+        self.issue(WebDriverCommand::GetElementText).and_then(|v| match v {
+            Json::String(v) => Ok(v),
+            v => Err(error::CmdError::NotW3C(v)),
+        })
+    }
+
+    /// Retrieve the tag name of this element (e.g., `div`).
+    pub fn tag_name(&mut self) -> impl Future<Item = String, Error = error::CmdError> {
+        self.issue(WebDriverCommand::GetElementTagName).and_then(|v| match v {
             Json::String(v) => Ok(v),
             v => Err(error::CmdError::NotW3C(v)),
         })
     }
 
+    /// Check that this element has the given tag name (e.g., `div`), and give it back if so.
+    ///
+    /// Tag names are compared case-insensitively. If the tag name does not match, an `unknown error`
+    /// that names both the expected and the actual tag is returned instead. This makes it easy to
+    /// assert what kind of element a locator found before going on to use it.
+    pub fn expect_tag(mut self, tag: &str) -> impl Future<Item = Self, Error = error::CmdError> {
+        let tag = tag.to_string();
+        self.tag_name().and_then(move |actual| {
+            if actual.eq_ignore_ascii_case(&tag) {
+                Ok(self)
+            } else {
+                let e = WebDriverError::new(
+                    webdriver::error::ErrorStatus::UnknownError,
+                    format!("expected a <{}> element, but found <{}>", tag, actual),
+                );
+                Err(error::CmdError::Standard(e))
+            }
+        })
+    }
+
+    /// Check whether this element is [stale], i.e., no longer attached to the current page.
+    ///
+    /// This is determined by attempting to read the element's tag name; if that fails with a
+    /// "stale element reference" error, the element is stale. Any other error is returned as-is.
+    ///
+    /// [stale]: https://www.w3.org/TR/webdriver/#dfn-stale-element-reference
+    pub fn is_stale(&mut self) -> impl Future<Item = bool, Error = error::CmdError> {
+        // NOTE: not self.tag_name(), which may find the element again if it is stale
+        let cmd = WebDriverCommand::GetElementTagName(self.web_element());
+        self.c.issue(cmd).then(|r| match r {
+            Ok(_) => Ok(false),
+            Err(error::CmdError::Standard(WebDriverError {
+                error: webdriver::error::ErrorStatus::StaleElementReference,
+                ..
+            })) => Ok(true),
+            Err(e) => Err(e),
+        })
+    }
+
     /// Retrieve the HTML contents of this element.
     ///
     /// `inner` dictates whether the wrapping node's HTML is excluded or not. For example, take the
@@ -1044,10 +3793,8 @@ impl Element {
     ///
     /// Note that since this *may* result in navigation, we give up the handle to the element.
     pub fn click(self) -> impl Future<Item = Client, Error = error::CmdError> {
-        let e = self.e;
-        let mut c = self.c;
-        let cmd = WebDriverCommand::ElementClick(e);
-        c.issue(cmd).and_then(move |r| {
+        let c = self.c.clone();
+        self.issue(WebDriverCommand::ElementClick).and_then(move |r| {
             if r.is_null() || r.as_object().map(|o| o.is_empty()).unwrap_or(false) {
                 // geckodriver returns {} :(
                 Ok(c)
@@ -1059,8 +3806,7 @@ impl Element {
 
     /// Clear the value prop of this element
     pub fn clear(&mut self) -> impl Future<Item = (), Error = error::CmdError> {
-        let cmd = WebDriverCommand::ElementClear(self.e.clone());
-        self.c.issue(cmd).and_then(move |r| {
+        self.issue(WebDriverCommand::ElementClear).and_then(move |r| {
             if r.is_null() {
                 Ok(())
             } else {
@@ -1069,15 +3815,70 @@ impl Element {
         })
     }
 
+    /// Clear the value of this element by selecting all its text with the keyboard, and then
+    /// deleting it.
+    ///
+    /// This clicks the element to focus it, presses Ctrl+A (Cmd+A on macOS), and then Backspace.
+    /// It can be used instead of `clear` for inputs where the native clear misbehaves, such as
+    /// inputs managed by JavaScript frameworks that do not notice the value being reset.
+    pub fn clear_via_keyboard(&mut self) -> impl Future<Item = (), Error = error::CmdError> {
+        // see https://www.w3.org/TR/webdriver/#keyboard-actions
+        const BACKSPACE: char = '\u{E003}';
+
+        let actions = ActionChain::new().move_to_element(self).click();
+        let actions = self
+            .select_all_shortcut(actions)
+            .send_keys(&BACKSPACE.to_string());
+        self.c.perform_actions(actions)
+    }
+
+    /// Clear the content of this element, whether it is a form input or a `contenteditable`
+    /// region.
+    ///
+    /// `clear` only works on form inputs, so for elements that are editable through the
+    /// [`contenteditable`] attribute, this selects all their content with the keyboard and
+    /// deletes it, like `clear_via_keyboard` does. Other elements are cleared with `clear`.
+    ///
+    /// [`contenteditable`]: https://developer.mozilla.org/en-US/docs/Web/HTML/Global_attributes/contenteditable
+    pub fn clear_editable(&mut self) -> impl Future<Item = (), Error = error::CmdError> {
+        let args = vec![via_json!(&self.web_element())];
+        let mut this = self.clone();
+        self.c
+            .execute("return arguments[0].isContentEditable;", args)
+            .and_then(move |editable| match editable {
+                Json::Bool(true) => future::Either::A(future::Either::A(this.clear_via_keyboard())),
+                Json::Bool(false) => future::Either::A(future::Either::B(this.clear())),
+                v => future::Either::B(future::err(error::CmdError::NotW3C(v))),
+            })
+    }
+
+    /// Select all of the text in this element.
+    ///
+    /// This focuses the element, and then presses Ctrl+A (Cmd+A on macOS), so that whatever is
+    /// typed next replaces the element's current content.
+    pub fn select_all(&mut self) -> impl Future<Item = (), Error = error::CmdError> {
+        let actions = self.select_all_shortcut(ActionChain::new());
+        let mut c = self.c.clone();
+        self.focus().and_then(move |_| c.perform_actions(actions))
+    }
+
+    /// Add the platform's select-all keyboard shortcut to the given actions.
+    fn select_all_shortcut(&self, actions: ActionChain) -> ActionChain {
+        // see https://www.w3.org/TR/webdriver/#keyboard-actions
+        const CONTROL: char = '\u{E009}';
+        const META: char = '\u{E03D}';
+
+        let modifier = if self.c.is_mac() { META } else { CONTROL };
+        actions.key_down(modifier).send_keys("a").key_up(modifier)
+    }
+
     /// Simulate the user sending keys to an element.
     pub fn send_keys(&mut self, text: &str) -> impl Future<Item = (), Error = error::CmdError> {
-        let cmd = WebDriverCommand::ElementSendKeys(
-            self.e.clone(),
-            SendKeysParameters {
-                text: text.to_owned(),
-            },
-        );
-        self.c.issue(cmd).and_then(move |r| {
+        let text = text.to_owned();
+        let cmd = move |e| {
+            WebDriverCommand::ElementSendKeys(e, SendKeysParameters { text: text.clone() })
+        };
+        self.issue(cmd).and_then(move |r| {
             if r.is_null() {
                 Ok(())
             } else {
@@ -1086,9 +3887,291 @@ impl Element {
         })
     }
 
-    /// Get back the [`Client`] hosting this `Element`.
-    pub fn client(self) -> Client {
-        self.c
+    /// Replace the value of this input element with `text`.
+    ///
+    /// This clears the element, and then types `text` using `send_keys`. If typing fails part of
+    /// the way through, the element is cleared again before the error is returned, so that the
+    /// field is never left half-filled and a retry starts from a clean slate.
+    pub fn refill(&mut self, text: &str) -> impl Future<Item = (), Error = error::CmdError> {
+        let text = text.to_string();
+        let mut this = self.clone();
+        self.clear().and_then(move |_| {
+            let mut cleanup = this.clone();
+            this.send_keys(&text).or_else(move |e| {
+                // report why typing failed, even if clearing the field fails too
+                cleanup.clear().then(move |_| Err(e))
+            })
+        })
+    }
+
+    /// Replace the value of this input element with `text`, and check that the page noticed.
+    ///
+    /// This clears the element and types `text` using `send_keys`. Inputs controlled by
+    /// JavaScript frameworks sometimes swallow the simulated key presses, so the element's `value`
+    /// is then read back. If it does not match `text`, the value is set once more using
+    /// JavaScript, followed by `input` and `change` events so that frameworks pick up the change.
+    /// If the value still does not match after that, a `CmdError::Standard` error whose status is
+    /// `ErrorStatus::InvalidElementState` is returned.
+    pub fn type_text(&mut self, text: &str) -> impl Future<Item = (), Error = error::CmdError> {
+        let text = text.to_string();
+        let mut this = self.clone();
+        self.clear()
+            .and_then(move |_| this.send_keys(&text).map(move |_| (this, text)))
+            .and_then(|(mut this, text)| this.prop("value").map(move |value| (this, text, value)))
+            .and_then(|(mut this, text, value)| {
+                if value.as_ref() == Some(&text) {
+                    return future::Either::A(future::ok(()));
+                }
+
+                // set the value through the prototype's setter, which frameworks such as React
+                // watch for, rather than the element's own (possibly overridden) property
+                let script = "var el = arguments[0]; \
+                              var proto = Object.getPrototypeOf(el); \
+                              var desc = Object.getOwnPropertyDescriptor(proto, 'value'); \
+                              if (desc && desc.set) { desc.set.call(el, arguments[1]); } \
+                              else { el.value = arguments[1]; } \
+                              el.dispatchEvent(new Event('input', { bubbles: true })); \
+                              el.dispatchEvent(new Event('change', { bubbles: true }));";
+                let args = vec![via_json!(&this.web_element()), Json::from(text.clone())];
+                let mut check = this.clone();
+                future::Either::B(
+                    this.c
+                        .execute(script, args)
+                        .and_then(move |_| check.prop("value"))
+                        .and_then(move |value| {
+                            if value.as_ref() == Some(&text) {
+                                Ok(())
+                            } else {
+                                let e = WebDriverError::new(
+                                    webdriver::error::ErrorStatus::InvalidElementState,
+                                    format!(
+                                        "element value is {:?} instead of {:?} after typing",
+                                        value, text
+                                    ),
+                                );
+                                Err(error::CmdError::Standard(e))
+                            }
+                        }),
+                )
+            })
+    }
+
+    /// Simulate the user sending keys to an element, sending at most `chunk_size` characters to
+    /// the WebDriver server at a time.
+    ///
+    /// Some drivers fail when asked to type very long strings in one go. This splits `text` into
+    /// chunks that are sent one after the other. Chunks only ever end at character boundaries, so
+    /// characters outside the Basic Multilingual Plane (such as emoji) are never split in half.
+    pub fn send_keys_chunked(
+        &mut self,
+        text: &str,
+        chunk_size: usize,
+    ) -> impl Future<Item = (), Error = error::CmdError> {
+        if chunk_size == 0 {
+            return future::Either::B(future::err(error::CmdError::InvalidArgument(
+                "chunk_size".to_string(),
+                "Expected to be `> 0` but was `0`".to_string(),
+            )));
+        }
+
+        let chars: Vec<char> = text.chars().collect();
+        let chunks: Vec<String> = chars
+            .chunks(chunk_size)
+            .map(|chunk| chunk.iter().collect())
+            .collect();
+        let mut this = self.clone();
+        future::Either::A(
+            futures::stream::iter_ok(chunks).for_each(move |chunk| this.send_keys(&chunk)),
+        )
+    }
+
+    /// Select the given local files in this `<input type="file">` element.
+    ///
+    /// Several files can only be selected at once if the input has the `multiple` attribute.
+    /// Relative paths are resolved against the current working directory, since the WebDriver
+    /// server reads the files itself and needs absolute paths. This means that the files must be
+    /// accessible to the machine the WebDriver server runs on.
+    pub fn send_files(
+        &mut self,
+        paths: &[&std::path::Path],
+    ) -> impl Future<Item = (), Error = error::CmdError> {
+        if paths.is_empty() {
+            return future::Either::B(future::err(error::CmdError::InvalidArgument(
+                "paths".to_string(),
+                "at least one file must be given".to_string(),
+            )));
+        }
+
+        let mut files = Vec::with_capacity(paths.len());
+        for path in paths {
+            match absolute_path(path, "paths") {
+                Ok(path) => files.push(path),
+                Err(e) => return future::Either::B(future::err(e)),
+            }
+        }
+
+        // the spec has file inputs take one path per line
+        future::Either::A(self.send_keys(&files.join("\n")))
+    }
+
+    /// Give this element keyboard focus.
+    pub fn focus(&mut self) -> impl Future<Item = (), Error = error::CmdError> {
+        self.c
+            .execute(
+                "arguments[0].focus();",
+                vec![via_json!(&self.web_element())],
+            )
+            .map(|_| ())
+    }
+
+    /// Remove keyboard focus from this element.
+    pub fn blur(&mut self) -> impl Future<Item = (), Error = error::CmdError> {
+        self.c
+            .execute("arguments[0].blur();", vec![via_json!(&self.web_element())])
+            .map(|_| ())
+    }
+
+    /// Move the mouse over the center of this element, without pressing any buttons.
+    ///
+    /// This is useful to trigger hover effects, such as menus that only appear on mouseover.
+    pub fn hover(&mut self) -> impl Future<Item = (), Error = error::CmdError> {
+        let actions = ActionChain::new().move_to_element(self);
+        self.c.perform_actions(actions)
+    }
+
+    /// Click at the point `(dx, dy)` pixels away from the top-left corner of this element.
+    ///
+    /// This is useful for elements whose content is not made up of other elements, such as
+    /// `<canvas>`. The element is scrolled into view first. Since the mouse can only be moved by
+    /// whole pixels, the point is rounded to the nearest pixel.
+    pub fn click_at(&mut self, dx: f64, dy: f64) -> impl Future<Item = (), Error = error::CmdError> {
+        let script = "\
+                      arguments[0].scrollIntoView({ block: 'nearest', inline: 'nearest' });\
+                      var r = arguments[0].getBoundingClientRect();\
+                      return [r.width, r.height]";
+        let mut c = self.c.clone();
+        let this = self.clone();
+        self.c
+            .execute(script, vec![via_json!(&self.web_element())])
+            .and_then(|rect| {
+                let size = rect.as_array().and_then(|r| {
+                    if r.len() == 2 {
+                        Some((r[0].as_f64()?, r[1].as_f64()?))
+                    } else {
+                        None
+                    }
+                });
+                match size {
+                    Some(size) => Ok(size),
+                    None => Err(error::CmdError::NotW3C(rect)),
+                }
+            })
+            .and_then(move |(width, height)| {
+                // pointer moves relative to an element are relative to its center
+                let x = (dx - width / 2.0).round() as i64;
+                let y = (dy - height / 2.0).round() as i64;
+                let actions = ActionChain::new()
+                    .move_to_element_center_offset(&this, x, y)
+                    .click();
+                c.perform_actions(actions)
+            })
+    }
+
+    /// Look up the [computed value] of each of the given CSS properties of this element.
+    ///
+    /// The values are returned in the same order as `properties`, and are all read with a single
+    /// script, rather than taking a round-trip to the browser per property. Properties that do not
+    /// apply to the element (or do not exist) have an empty value.
+    ///
+    /// [computed value]: https://developer.mozilla.org/en-US/docs/Web/API/Window/getComputedStyle
+    pub fn computed_styles(
+        &mut self,
+        properties: &[&str],
+    ) -> impl Future<Item = Vec<String>, Error = error::CmdError> {
+        let script = "\
+                      var style = window.getComputedStyle(arguments[0]);\
+                      return arguments[1].map(function(p) { return style.getPropertyValue(p); });";
+        let args = vec![via_json!(&self.web_element()), Json::from(properties)];
+        self.c.execute(script, args).and_then(|v| {
+            let values = v.as_array().and_then(|values| {
+                values
+                    .iter()
+                    .map(|value| value.as_str().map(String::from))
+                    .collect::<Option<Vec<_>>>()
+            });
+            match values {
+                Some(values) => Ok(values),
+                None => Err(error::CmdError::NotW3C(v)),
+            }
+        })
+    }
+
+    /// Check whether any part of this element is currently within the visible viewport.
+    ///
+    /// Unlike most other methods, this does not scroll the element into view first, which makes
+    /// it useful for testing lazy loading. Note that an element that is in the viewport may still
+    /// be hidden, for example by another element on top of it.
+    pub fn in_viewport(&mut self) -> impl Future<Item = bool, Error = error::CmdError> {
+        let script = "\
+                      var r = arguments[0].getBoundingClientRect();\
+                      return r.bottom > 0 && r.right > 0 \
+                        && r.top < window.innerHeight && r.left < window.innerWidth;";
+        self.c
+            .execute(script, vec![via_json!(&self.web_element())])
+            .and_then(|v| match v {
+                Json::Bool(v) => Ok(v),
+                v => Err(error::CmdError::NotW3C(v)),
+            })
+    }
+
+    /// Scroll this element into view, and get the position of its top-left corner relative to
+    /// the viewport, in CSS pixels.
+    ///
+    /// The page is only scrolled as far as needed to bring the element into view, if at all.
+    pub fn location_in_view(&mut self) -> impl Future<Item = (f64, f64), Error = error::CmdError> {
+        let script = "\
+                      arguments[0].scrollIntoView({ block: 'nearest', inline: 'nearest' });\
+                      var r = arguments[0].getBoundingClientRect();\
+                      return [r.left, r.top]";
+        self.c
+            .execute(script, vec![via_json!(&self.web_element())])
+            .and_then(|v| {
+                let location = v.as_array().and_then(|l| {
+                    if l.len() == 2 {
+                        Some((l[0].as_f64()?, l[1].as_f64()?))
+                    } else {
+                        None
+                    }
+                });
+                match location {
+                    Some(location) => Ok(location),
+                    None => Err(error::CmdError::NotW3C(v)),
+                }
+            })
+    }
+
+    /// Get back the [`Client`] hosting this `Element`.
+    pub fn client(self) -> Client {
+        self.c
+    }
+
+    /// Switch the focus to the frame contained in this element.
+    ///
+    /// Since this changes what elements are reachable, we give up the handle to the element.
+    pub fn enter_frame(self) -> impl Future<Item = Client, Error = error::CmdError> {
+        let params = webdriver::command::SwitchToFrameParameters {
+            id: Some(webdriver::common::FrameId::Element(self.web_element())),
+        };
+        let mut c = self.c;
+        c.issue(WebDriverCommand::SwitchToFrame(params))
+            .and_then(move |r| {
+                if r.is_null() || r.as_object().map(|o| o.is_empty()).unwrap_or(false) {
+                    // geckodriver returns {} :(
+                    Ok(c)
+                } else {
+                    Err(error::CmdError::NotW3C(r))
+                }
+            })
     }
 
     /// Follow the `href` target of the element matching the given CSS selector *without* causing a
@@ -1096,7 +4179,7 @@ impl Element {
     ///
     /// Note that since this *may* result in navigation, we give up the handle to the element.
     pub fn follow(self) -> impl Future<Item = Client, Error = error::CmdError> {
-        let e = self.e;
+        let e = self.web_element();
         let mut c = self.c;
         let cmd = WebDriverCommand::GetElementAttribute(e, "href".to_string());
         c.issue(cmd)
@@ -1129,29 +4212,213 @@ impl Element {
             value: locator,
         };
 
-        let e = self.e;
-        let mut c = self.c;
-        let cmd = WebDriverCommand::FindElementElement(e, locator);
-        c.issue(cmd)
-            .and_then(move |v| parse_lookup(v, c.is_legacy()).map(move |e| Element { c: c, e }))
-            .and_then(move |e| e.click())
+        by(self.c.clone(), locator, Some(&self)).and_then(move |e| e.click())
     }
 
     /// Check element for displayed
     pub fn displayed(&self) -> impl Future<Item = bool, Error = error::CmdError> {
-        let cmd = WebDriverCommand::IsDisplayed(self.e.clone());
-        self.c.clone().issue(cmd).and_then(|v| match v {
+        self.issue(WebDriverCommand::IsDisplayed).and_then(|v| match v {
+            Json::Bool(v) => Ok(v),
+            v => Err(error::CmdError::NotW3C(v)),
+        })
+    }
+
+    /// Get a PNG-encoded screenshot of this element.
+    ///
+    /// Note that some drivers (notably chromedriver) only capture the part of the element that
+    /// fits in the viewport. See `screenshot_full` for a way around that.
+    pub fn screenshot(&mut self) -> impl Future<Item = Vec<u8>, Error = error::CmdError> {
+        let cmd = WebDriverCommand::TakeElementScreenshot(self.web_element());
+        self.c.issue(cmd).and_then(|src| {
+            if let Some(src) = src.as_str() {
+                return base64::decode(src).map_err(|e| error::CmdError::ImageDecodeError(e));
+            }
+
+            Err(error::CmdError::NotW3C(src))
+        })
+    }
+
+    /// Save a PNG-encoded screenshot of this element to the file at `path`, replacing it if it
+    /// already exists.
+    ///
+    /// The screenshot is taken like `screenshot` does, and a failure to write the file gives
+    /// `CmdError::Io`.
+    pub fn save_screenshot(
+        &mut self,
+        path: &std::path::Path,
+    ) -> impl Future<Item = (), Error = error::CmdError> {
+        let path = path.to_path_buf();
+        self.screenshot().and_then(move |png| write_file(path, png))
+    }
+
+    /// Get a PNG-encoded screenshot of this entire element, even if it overflows the viewport.
+    ///
+    /// On Chromium, this captures the element's bounding box using the DevTools
+    /// `Page.captureScreenshot` command. Other browsers already capture the whole element, so
+    /// this is equivalent to `screenshot` there.
+    pub fn screenshot_full(&mut self) -> impl Future<Item = Vec<u8>, Error = error::CmdError> {
+        if !self.c.is_chromium() {
+            return future::Either::A(self.screenshot());
+        }
+
+        // the clip rectangle is in document coordinates, not viewport coordinates
+        let script = "\
+                      var r = arguments[0].getBoundingClientRect();\
+                      return [r.left + window.pageXOffset, r.top + window.pageYOffset,\
+                              r.width, r.height]";
+        let args = vec![via_json!(&self.web_element())];
+        future::Either::B(self.c.clipped_screenshot(script, args, true))
+    }
+
+    /// Get a PNG-encoded screenshot of this element, using the given options.
+    ///
+    /// With the default options, this is equivalent to `screenshot`. Leaving the element where it
+    /// is (i.e., not scrolling it into view) is only supported on Chromium, where it uses the
+    /// DevTools `Page.captureScreenshot` command. Other browsers fail with a `CmdError::Standard`
+    /// error whose status is `ErrorStatus::UnsupportedOperation`. If none of the element is in the
+    /// viewport, the status is `ErrorStatus::UnableToCaptureScreen`.
+    pub fn screenshot_opts(
+        &mut self,
+        opts: ScreenshotOptions,
+    ) -> impl Future<Item = Vec<u8>, Error = error::CmdError> {
+        if opts.scroll {
+            return future::Either::A(future::Either::A(self.screenshot()));
+        }
+
+        if !self.c.is_chromium() {
+            let e = WebDriverError::new(
+                webdriver::error::ErrorStatus::UnsupportedOperation,
+                "screenshots without scrolling are only supported on Chromium",
+            );
+            return future::Either::B(future::err(error::CmdError::Standard(e)));
+        }
+
+        // only capture the part of the element that is in the viewport
+        let script = "\
+                      var r = arguments[0].getBoundingClientRect();\
+                      var left = Math.max(r.left, 0);\
+                      var top = Math.max(r.top, 0);\
+                      var right = Math.min(r.right, window.innerWidth);\
+                      var bottom = Math.min(r.bottom, window.innerHeight);\
+                      if (right <= left || bottom <= top) { return null; }\
+                      return [left + window.pageXOffset, top + window.pageYOffset,\
+                              right - left, bottom - top]";
+        let args = vec![via_json!(&self.web_element())];
+        let shot = self.c.clipped_screenshot(script, args, false);
+        future::Either::A(future::Either::B(shot))
+    }
+
+    /// Get the computed [WAI-ARIA role] of this element (e.g., `"button"`).
+    ///
+    /// Drivers that predate this command fail with a `CmdError::Standard` error whose status is
+    /// `ErrorStatus::UnknownCommand`.
+    ///
+    /// [WAI-ARIA role]: https://www.w3.org/TR/webdriver/#get-computed-role
+    pub fn aria_role(&mut self) -> impl Future<Item = String, Error = error::CmdError> {
+        let cmd = ExtensionCommand::GetComputedRole(self.web_element());
+        self.c
+            .issue(WebDriverCommand::Extension(cmd))
+            .and_then(|v| match v {
+                Json::String(v) => Ok(v),
+                v => Err(error::CmdError::NotW3C(v)),
+            })
+    }
+
+    /// Get the computed [accessible name] of this element.
+    ///
+    /// Drivers that predate this command fail with a `CmdError::Standard` error whose status is
+    /// `ErrorStatus::UnknownCommand`.
+    ///
+    /// [accessible name]: https://www.w3.org/TR/webdriver/#get-computed-label
+    pub fn aria_label(&mut self) -> impl Future<Item = String, Error = error::CmdError> {
+        let cmd = ExtensionCommand::GetComputedLabel(self.web_element());
+        self.c
+            .issue(WebDriverCommand::Extension(cmd))
+            .and_then(|v| match v {
+                Json::String(v) => Ok(v),
+                v => Err(error::CmdError::NotW3C(v)),
+            })
+    }
+
+    /// Check whether this element is enabled.
+    ///
+    /// See <https://www.w3.org/TR/webdriver/#is-element-enabled>.
+    pub fn is_enabled(&mut self) -> impl Future<Item = bool, Error = error::CmdError> {
+        self.issue(WebDriverCommand::IsEnabled).and_then(|v| match v {
             Json::Bool(v) => Ok(v),
             v => Err(error::CmdError::NotW3C(v)),
         })
     }
 
+    /// Wait for this element to become enabled.
+    ///
+    /// If the element is still disabled after `timeout`, `CmdError::WaitTimeout` is returned.
+    pub fn wait_until_enabled(
+        &mut self,
+        timeout: Duration,
+    ) -> impl Future<Item = (), Error = error::CmdError> {
+        let wait = future::loop_fn(self.clone(), |mut e| {
+            e.is_enabled().and_then(move |enabled| {
+                if enabled {
+                    return future::Either::A(future::ok(future::Loop::Break(())));
+                }
+                future::Either::B(sleep(POLL_INTERVAL).map(move |_| future::Loop::Continue(e)))
+            })
+        });
+        wait_with_timeout(wait, timeout)
+    }
+
     /// Find an element on the page.
     pub fn find(
         &self,
         search: Locator,
     ) -> impl Future<Item = Element, Error = error::CmdError> {
-        by(self.c.clone(), search.into(), Some(self))
+        if let Err(e) = search.check() {
+            return future::Either::B(future::err(e));
+        }
+        future::Either::A(by(self.c.clone(), search.into(), Some(self)))
+    }
+
+    /// Get the text content of each descendant of this element that matches the given locator,
+    /// in document order.
+    ///
+    /// Unlike calling `find_all` and then `text` on each of the elements, this takes a single
+    /// round-trip to the WebDriver server. Note that this gives each element's [`textContent`],
+    /// which, unlike `text`, includes text that is not rendered (e.g., because it is hidden).
+    ///
+    /// [`textContent`]: https://developer.mozilla.org/en-US/docs/Web/API/Node/textContent
+    pub fn texts(
+        &mut self,
+        search: Locator,
+    ) -> impl Future<Item = Vec<String>, Error = error::CmdError> {
+        if let Err(e) = search.check() {
+            return future::Either::B(future::err(e));
+        }
+        let locator: webdriver::command::LocatorParameters = search.into();
+        let locator = match serde_json::to_value(&locator) {
+            Ok(locator) => locator,
+            Err(e) => return future::Either::B(future::err(e.into())),
+        };
+
+        let script = format!(
+            "var root = arguments[0], using = arguments[1], value = arguments[2];\
+             {}\
+             return found.map(function(e) {{ return e.textContent; }});",
+            FIND_IN_SCRIPT
+        );
+        let args = vec![via_json!(&self.web_element()), locator["using"].clone(), locator["value"].clone()];
+        future::Either::A(self.c.execute(&script, args).and_then(|v| {
+            let texts = v.as_array().and_then(|texts| {
+                texts
+                    .iter()
+                    .map(|text| text.as_str().map(String::from))
+                    .collect::<Option<Vec<_>>>()
+            });
+            match texts {
+                Some(texts) => Ok(texts),
+                None => Err(error::CmdError::NotW3C(v)),
+            }
+        }))
     }
 
     /// Find elements on the page.
@@ -1159,7 +4426,49 @@ impl Element {
         &self,
         search: Locator,
     ) -> impl Future<Item = Vec<Element>, Error = error::CmdError> {
-        find_all(self.c.clone(), search, Some(self))
+        if let Err(e) = search.check() {
+            return future::Either::B(future::err(e));
+        }
+        future::Either::A(find_all(self.c.clone(), search.into(), Some(self)))
+    }
+
+    // helpers
+
+    /// The WebDriver reference to this element.
+    pub(crate) fn web_element(&self) -> webdriver::common::WebElement {
+        self.e.lock().unwrap().clone()
+    }
+
+    /// Issue the command that `cmd` produces for this element.
+    ///
+    /// If the element turns out to be stale, and the client is set to retry stale elements (see
+    /// `Client::retry_stale_elements`), the element is found again using the locator it was
+    /// originally found with, and the command is retried once with the fresh element. The fresh
+    /// element then replaces the stale one for this element and all its clones.
+    fn issue<F>(&self, cmd: F) -> impl Future<Item = Json, Error = error::CmdError>
+    where
+        F: Fn(webdriver::common::WebElement) -> WebDriverCommand<ExtensionCommand> + 'static,
+    {
+        let mut c = self.c.clone();
+        let origin = if c.retries_stale_elements() {
+            self.origin.clone()
+        } else {
+            None
+        };
+        let e = self.e.clone();
+        c.issue(cmd(self.web_element())).or_else(move |err| match (err, origin) {
+            (
+                error::CmdError::Standard(WebDriverError {
+                    error: webdriver::error::ErrorStatus::StaleElementReference,
+                    ..
+                }),
+                Some(origin),
+            ) => future::Either::A(origin.find(c.clone()).and_then(move |fresh| {
+                *e.lock().unwrap() = fresh.clone();
+                c.issue(cmd(fresh))
+            })),
+            (err, _) => future::Either::B(future::err(err)),
+        })
     }
 }
 
@@ -1170,11 +4479,15 @@ impl Form {
         locator: Locator,
         value: &str,
     ) -> impl Future<Item = Self, Error = error::CmdError> {
+        if let Err(e) = locator.check() {
+            return future::Either::B(future::err(e));
+        }
         let locator = WebDriverCommand::FindElementElement(self.f.clone(), locator.into());
         let f = self.f.clone();
         let this = self.c.clone();
         let value = Json::from(value);
-        self.c
+        let set = self
+            .c
             .issue(locator)
             .and_then(move |res| {
                 let f = parse_lookup(res, this.is_legacy());
@@ -1197,7 +4510,8 @@ impl Form {
                 } else {
                     Err(error::CmdError::NotW3C(res))
                 }
-            })
+            });
+        future::Either::A(set)
     }
 
     /// Find a form input with the given `name` and set its value to `value`.
@@ -1218,6 +4532,48 @@ impl Form {
         self.submit_with(Locator::Css("input[type=submit],button[type=submit]"))
     }
 
+    /// Submit this form using the first available submit button, and wait for the resulting page
+    /// to finish loading.
+    ///
+    /// The wait is over once the form is no longer on the page, and the new page's
+    /// `document.readyState` is `complete`. If that does not happen within `timeout`,
+    /// `CmdError::WaitTimeout` is returned.
+    pub fn submit_and_wait(
+        self,
+        timeout: Duration,
+    ) -> impl Future<Item = Client, Error = error::CmdError> {
+        let form = Element {
+            c: self.c.clone(),
+            e: Arc::new(Mutex::new(self.f.clone())),
+            origin: None,
+        };
+        let wait = self.submit().and_then(move |c| {
+            future::loop_fn((c, form), |(mut c, mut form)| {
+                form.is_stale()
+                    .and_then(move |stale| {
+                        if !stale {
+                            // we have not left the page yet
+                            return future::Either::A(future::ok((c, form, false)));
+                        }
+
+                        future::Either::B(
+                            c.execute("return document.readyState", vec![])
+                                .map(move |state| (c, form, state == "complete")),
+                        )
+                    })
+                    .and_then(|(c, form, loaded)| {
+                        if loaded {
+                            return future::Either::A(future::ok(future::Loop::Break(c)));
+                        }
+                        future::Either::B(
+                            sleep(POLL_INTERVAL).map(move |_| future::Loop::Continue((c, form))),
+                        )
+                    })
+            })
+        });
+        wait_with_timeout(wait, timeout)
+    }
+
     /// Submit this form using the button matched by the given selector.
     ///
     /// `false` is returned if a matching button was not found.
@@ -1339,55 +4695,436 @@ impl Form {
     }
 }
 
+impl RawResponse {
+    /// The HTTP status code of the response.
+    pub fn status(&self) -> hyper::StatusCode {
+        self.inner.status()
+    }
+
+    /// The HTTP headers of the response.
+    pub fn headers(&self) -> &hyper::HeaderMap {
+        self.inner.headers()
+    }
+
+    /// Read the entire response body.
+    pub fn bytes(self) -> impl Future<Item = Vec<u8>, Error = error::CmdError> {
+        self.inner
+            .into_body()
+            .concat2()
+            .map(|body| body.to_vec())
+            .map_err(error::CmdError::from)
+    }
+
+    /// Read the entire response body as text.
+    ///
+    /// Any invalid UTF-8 sequences in the body are replaced with `U+FFFD`.
+    pub fn text(self) -> impl Future<Item = String, Error = error::CmdError> {
+        self.bytes()
+            .map(|body| String::from_utf8_lossy(&body).into_owned())
+    }
+
+    /// Get back the underlying `hyper` response.
+    pub fn into_inner(self) -> hyper::Response<hyper::Body> {
+        self.inner
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
 
-    macro_rules! tester {
-        ($f:ident, $endpoint:expr) => {{
-            use std::sync::{Arc, Mutex};
-            use std::thread;
-            let c = match $endpoint {
-                "firefox" => {
-                    let mut caps = serde_json::map::Map::new();
-                    let opts = serde_json::json!({ "args": ["--headless"] });
-                    caps.insert("moz:firefoxOptions".to_string(), opts.clone());
-                    Client::with_capabilities("http://localhost:4444", caps)
-                },
-                "chrome" => {
-                    let mut caps = serde_json::map::Map::new();
-                    let opts = serde_json::json!({
-                        "args": ["--headless", "--disable-gpu", "--no-sandbox", "--disable-dev-shm-usage"],
-                        "binary":
-                            if std::path::Path::new("/usr/bin/chromium-browser").exists() {
-                                // on Ubuntu, it's called chromium-browser
-                                "/usr/bin/chromium-browser"
-                            } else if std::path::Path::new("/Applications/Google Chrome.app/Contents/MacOS/Google Chrome").exists() {
-                                // macOS
-                                "/Applications/Google Chrome.app/Contents/MacOS/Google Chrome"
-                            } else {
-                                // elsewhere, it's just called chromium
-                                "/usr/bin/chromium"
-                            }
-                    });
-                    caps.insert("goog:chromeOptions".to_string(), opts.clone());
+    #[test]
+    fn it_parses_page_ranges() {
+        let mut opts = PrintOptions::default();
+        opts.page_ranges_from_str("1-3,5").unwrap();
+        assert_eq!(opts.to_json()["pageRanges"], serde_json::json!(["1-3", 5]));
 
-                    Client::with_capabilities("http://localhost:9515", caps)
-                },
-                browser => unimplemented!("unsupported browser backend {}", browser),
+        opts.page_ranges_from_str(" 8- , -2").unwrap();
+        assert_eq!(
+            opts.page_ranges,
+            vec![PageRange::Range(Some(8), None), PageRange::Range(None, Some(2))]
+        );
+
+        for bad in &["", "1,,2", "a-3", "0", "3-1", "-"] {
+            assert!(opts.page_ranges_from_str(bad).is_err(), "{:?} should not parse", bad);
+        }
+        // a failed parse leaves the old ranges in place
+        assert_eq!(opts.page_ranges.len(), 2);
+    }
+
+    /// Spawn an HTTP server on a thread of its own, which answers every request using `service`.
+    ///
+    /// Returns the URL of the server.
+    fn spawn_http<F, R>(service: F) -> String
+    where
+        F: Fn(hyper::Request<hyper::Body>) -> R + Send + Sync + 'static,
+        R: IntoFuture<Item = hyper::Response<hyper::Body>, Error = hyper::Error>,
+        R::Future: Send + 'static,
+    {
+        use hyper::service::service_fn;
+        use hyper::Server;
+
+        let service = Arc::new(service);
+        let (tx, rx) = std::sync::mpsc::channel();
+        std::thread::spawn(move || {
+            let mut rt = tokio::runtime::current_thread::Runtime::new().unwrap();
+            rt.block_on(future::lazy(move || {
+                let server = Server::bind(&([127, 0, 0, 1], 0).into()).serve(move || {
+                    let service = service.clone();
+                    service_fn(move |req| (*service)(req).into_future())
+                });
+                tx.send(server.local_addr()).unwrap();
+                server
+            }))
+            .unwrap();
+        });
+        format!("http://{}/", rx.recv().unwrap())
+    }
+
+    /// Spawn a minimal stand-in for a WebDriver server, which rejects any request that does not
+    /// carry the given header.
+    ///
+    /// Returns the URL of the stub server.
+    fn spawn_stub_webdriver(header: &'static str, expected: &'static str) -> String {
+        use hyper::{Body, Response, StatusCode};
+
+        spawn_http(move |req| {
+            let authorized = req.headers().get(header).map(|v| v == expected) == Some(true);
+            let (status, value) = if !authorized {
+                (
+                    StatusCode::UNAUTHORIZED,
+                    serde_json::json!({
+                        "error": "unknown error",
+                        "message": format!("missing {} header", header),
+                        "stacktrace": "",
+                    }),
+                )
+            } else {
+                let value = match (req.method().as_str(), req.uri().path()) {
+                    ("POST", "/session") => {
+                        serde_json::json!({ "sessionId": "stub", "capabilities": {} })
+                    }
+                    ("GET", "/session/stub/url") => Json::from("about:blank"),
+                    _ => Json::Null,
+                };
+                (StatusCode::OK, value)
             };
+            let body = serde_json::json!({ "value": value }).to_string();
+            let rsp = Response::builder()
+                .status(status)
+                .header(hyper::header::CONTENT_TYPE, "application/json")
+                .body(Body::from(body))
+                .unwrap();
+            Ok::<_, hyper::Error>(rsp)
+        })
+    }
 
-            // we'll need the session_id from the thread
-            // NOTE: even if it panics, so can't just return it
-            let session_id = Arc::new(Mutex::new(None));
+    #[test]
+    fn it_converts_cookie_expiry() {
+        let cookie = parse_cookie(serde_json::json!({
+            "name": "session",
+            "value": "abc",
+            "expiry": 1_600_000_000,
+        }))
+        .unwrap();
+        assert_eq!(
+            cookie.expiry_time(),
+            Some(UNIX_EPOCH + Duration::from_secs(1_600_000_000))
+        );
 
-            // run test in its own thread to catch panics
-            let sid = session_id.clone();
-            let success = match thread::spawn(move || {
-                let mut rt = tokio::runtime::current_thread::Runtime::new().unwrap();
-                let mut c = rt.block_on(c).expect("failed to construct test client");
-                *sid.lock().unwrap() = rt.block_on(c.session_id()).unwrap();
-                let x = rt.block_on($f(c));
+        let cookie =
+            parse_cookie(serde_json::json!({ "name": "session", "value": "abc" })).unwrap();
+        assert_eq!(cookie.expiry_time(), None);
+    }
+
+    /// Spawn a minimal stand-in for a WebDriver server that only speaks the legacy JSON Wire
+    /// protocol.
+    ///
+    /// Returns the URL of the stub server.
+    fn spawn_legacy_webdriver() -> String {
+        use hyper::{Body, Response};
+
+        spawn_http(|req| {
+            let route = (req.method().clone(), req.uri().path().to_string());
+            req.into_body().concat2().map(move |body| {
+                let body: Json = serde_json::from_slice(&body).unwrap_or(Json::Null);
+                let (status, value) = match (route.0.as_str(), route.1.as_str()) {
+                    ("POST", "/session") if body.get("desiredCapabilities").is_some() => {
+                        (0, serde_json::json!({}))
+                    }
+                    ("POST", "/session") => {
+                        (33, serde_json::json!({ "message": "W3C is not supported" }))
+                    }
+                    ("GET", "/session/stub/url") => (0, Json::from("about:blank")),
+                    ("POST", "/session/stub/url") if body["url"].is_string() => (0, Json::Null),
+                    ("DELETE", "/session/stub") => (0, Json::Null),
+                    _ => (9, serde_json::json!({ "message": "unknown command" })),
+                };
+                let body = serde_json::json!({
+                    "sessionId": "stub",
+                    "status": status,
+                    "value": value,
+                });
+                Response::builder()
+                    .header(hyper::header::CONTENT_TYPE, "application/json")
+                    .body(Body::from(body.to_string()))
+                    .unwrap()
+            })
+        })
+    }
+
+    #[test]
+    #[ignore]
+    fn it_connects_with_default_capabilities() {
+        // NOTE: ignored since the driver's defaults usually mean a visible browser window
+        let mut rt = tokio::runtime::current_thread::Runtime::new().unwrap();
+        let c = rt
+            .block_on(Client::new("http://localhost:4444"))
+            .expect("failed to connect to a local geckodriver");
+        let mut c = rt.block_on(c.goto("data:text/html,hello")).unwrap();
+        rt.block_on(c.close()).unwrap();
+    }
+
+    #[test]
+    fn it_speaks_legacy_protocol() {
+        let mut rt = tokio::runtime::current_thread::Runtime::new().unwrap();
+        let url = spawn_legacy_webdriver();
+
+        let c = rt
+            .block_on(
+                ClientBuilder::new()
+                    .protocol(Protocol::Legacy)
+                    .connect(&url),
+            )
+            .expect("failed to connect using the legacy protocol");
+        let mut c = rt
+            .block_on(c.goto("http://example.com/"))
+            .expect("failed to navigate using the legacy protocol");
+        rt.block_on(c.close()).unwrap();
+    }
+
+    #[test]
+    fn it_sends_user_agent() {
+        let mut rt = tokio::runtime::current_thread::Runtime::new().unwrap();
+        let url = spawn_stub_webdriver("user-agent", "corp-client/1.0");
+
+        let mut c = rt
+            .block_on(ClientBuilder::new().user_agent("corp-client/1.0").connect(&url))
+            .expect("failed to connect with the required user agent");
+        let current = rt.block_on(c.current_url()).unwrap();
+        assert_eq!(current.as_str(), "about:blank");
+        rt.block_on(c.close()).unwrap();
+    }
+
+    #[test]
+    fn it_sends_custom_headers() {
+        let mut rt = tokio::runtime::current_thread::Runtime::new().unwrap();
+        let url = spawn_stub_webdriver("authorization", "Bearer hunter2");
+
+        // without the header, the gateway turns us away
+        match rt.block_on(Client::new(&url)) {
+            Err(error::NewSessionError::Standard(ref e)) => {
+                assert!(e.message.contains("authorization"))
+            }
+            Ok(_) => panic!("session was created without the required header"),
+            Err(e) => panic!("unexpected error: {}", e),
+        }
+
+        let mut c = rt
+            .block_on(
+                ClientBuilder::new()
+                    .header(
+                        hyper::header::AUTHORIZATION,
+                        hyper::header::HeaderValue::from_static("Bearer hunter2"),
+                    )
+                    .connect(&url),
+            )
+            .expect("failed to connect with the required header");
+        let current = rt.block_on(c.current_url()).unwrap();
+        assert_eq!(current.as_str(), "about:blank");
+        rt.block_on(c.close()).unwrap();
+    }
+
+    /// Spawn a minimal stand-in for a WebDriver server, which finds a single element that is
+    /// reported as stale (with the status code the spec prescribes) as soon as it is used.
+    ///
+    /// Returns the URL of the stub server.
+    fn spawn_stale_webdriver() -> String {
+        use hyper::{Body, Response, StatusCode};
+
+        spawn_http(|req| {
+            let (status, value) = match (req.method().as_str(), req.uri().path()) {
+                ("POST", "/session") => (
+                    StatusCode::OK,
+                    serde_json::json!({ "sessionId": "stub", "capabilities": {} }),
+                ),
+                ("POST", "/session/stub/element") => (
+                    StatusCode::OK,
+                    serde_json::json!({ ELEMENT_KEY: "stub-element" }),
+                ),
+                ("GET", "/session/stub/element/stub-element/name") => (
+                    StatusCode::NOT_FOUND,
+                    serde_json::json!({
+                        "error": "stale element reference",
+                        "message": "element is not attached to the page document",
+                        "stacktrace": "",
+                    }),
+                ),
+                _ => (StatusCode::OK, Json::Null),
+            };
+            let body = serde_json::json!({ "value": value }).to_string();
+            let rsp = Response::builder()
+                .status(status)
+                .header(hyper::header::CONTENT_TYPE, "application/json")
+                .body(Body::from(body))
+                .unwrap();
+            Ok::<_, hyper::Error>(rsp)
+        })
+    }
+
+    #[test]
+    fn it_detects_stale_elements_by_status() {
+        let mut rt = tokio::runtime::current_thread::Runtime::new().unwrap();
+        let url = spawn_stale_webdriver();
+
+        let mut c = rt
+            .block_on(Client::new(&url))
+            .expect("failed to connect to stub");
+        let mut e = rt.block_on(c.find(Locator::Css("p"))).unwrap();
+        assert!(rt.block_on(e.is_stale()).expect("stale probe failed"));
+        rt.block_on(c.close()).unwrap();
+    }
+
+    /// Spawn a minimal stand-in for a WebDriver server, which closes the connection without
+    /// responding the first time it is asked for the current URL.
+    ///
+    /// Returns the URL of the stub server.
+    fn spawn_flaky_webdriver() -> String {
+        use std::io::{BufRead, BufReader, Read, Write};
+
+        let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+        let url = format!("http://{}/", listener.local_addr().unwrap());
+        std::thread::spawn(move || {
+            let mut dropped = false;
+            for stream in listener.incoming() {
+                let mut reader = BufReader::new(stream.unwrap());
+                let mut request_line = String::new();
+                reader.read_line(&mut request_line).unwrap();
+                let mut length = 0;
+                loop {
+                    let mut header = String::new();
+                    reader.read_line(&mut header).unwrap();
+                    let header = header.trim().to_lowercase();
+                    if header.is_empty() {
+                        break;
+                    }
+                    if header.starts_with("content-length:") {
+                        length = header["content-length:".len()..].trim().parse().unwrap();
+                    }
+                }
+                let mut body = vec![0; length];
+                reader.read_exact(&mut body).unwrap();
+
+                let value = if request_line.starts_with("POST /session ") {
+                    serde_json::json!({ "sessionId": "stub", "capabilities": {} })
+                } else if request_line.starts_with("GET /session/stub/url ") {
+                    if !dropped {
+                        // hang up on the client
+                        dropped = true;
+                        continue;
+                    }
+                    Json::from("about:blank")
+                } else {
+                    Json::Null
+                };
+                let body = serde_json::json!({ "value": value }).to_string();
+                let mut stream = reader.into_inner();
+                write!(
+                    stream,
+                    "HTTP/1.1 200 OK\r\n\
+                     content-type: application/json\r\n\
+                     content-length: {}\r\n\
+                     connection: close\r\n\r\n{}",
+                    body.len(),
+                    body
+                )
+                .unwrap();
+            }
+        });
+        url
+    }
+
+    #[test]
+    fn it_retries_reads_on_connection_reset() {
+        let mut rt = tokio::runtime::current_thread::Runtime::new().unwrap();
+        let url = spawn_flaky_webdriver();
+
+        let mut c = rt
+            .block_on(Client::new(&url))
+            .expect("failed to connect to stub");
+        c.retry_on_connection_reset(true);
+        let current = rt
+            .block_on(c.current_url())
+            .expect("reading the current url was not retried");
+        assert_eq!(current.as_str(), "about:blank");
+        rt.block_on(c.close()).unwrap();
+    }
+
+    macro_rules! tester {
+        ($f:ident, $endpoint:expr) => {
+            tester!($f, $endpoint, |_| {})
+        };
+        ($f:ident, $endpoint:expr, $configure:expr) => {{
+            use std::sync::{Arc, Mutex};
+            use std::thread;
+            let (webdriver, caps) = match $endpoint {
+                "firefox" => {
+                    let mut caps = serde_json::map::Map::new();
+                    let opts = serde_json::json!({ "args": ["--headless"] });
+                    caps.insert("moz:firefoxOptions".to_string(), opts.clone());
+                    ("http://localhost:4444", caps)
+                },
+                "chrome" => {
+                    let mut caps = serde_json::map::Map::new();
+                    let opts = serde_json::json!({
+                        "args": ["--headless", "--disable-gpu", "--no-sandbox", "--disable-dev-shm-usage"],
+                        "binary":
+                            if std::path::Path::new("/usr/bin/chromium-browser").exists() {
+                                // on Ubuntu, it's called chromium-browser
+                                "/usr/bin/chromium-browser"
+                            } else if std::path::Path::new("/Applications/Google Chrome.app/Contents/MacOS/Google Chrome").exists() {
+                                // macOS
+                                "/Applications/Google Chrome.app/Contents/MacOS/Google Chrome"
+                            } else {
+                                // elsewhere, it's just called chromium
+                                "/usr/bin/chromium"
+                            }
+                    });
+                    caps.insert("goog:chromeOptions".to_string(), opts.clone());
+
+                    ("http://localhost:9515", caps)
+                },
+                browser => unimplemented!("unsupported browser backend {}", browser),
+            };
+
+            // let the test tweak how the client connects
+            let mut builder = ClientBuilder::new();
+            builder.capabilities(caps);
+            let configure: fn(&mut ClientBuilder) = $configure;
+            configure(&mut builder);
+            let c = builder.connect(webdriver);
+
+            // we'll need the session_id from the thread
+            // NOTE: even if it panics, so can't just return it
+            let session_id = Arc::new(Mutex::new(None));
+
+            // run test in its own thread to catch panics
+            let sid = session_id.clone();
+            let success = match thread::spawn(move || {
+                let mut rt = tokio::runtime::current_thread::Runtime::new().unwrap();
+                let mut c = rt.block_on(c).expect("failed to construct test client");
+                *sid.lock().unwrap() = rt.block_on(c.session_id()).unwrap();
+                let x = rt.block_on($f(c));
                 rt.run().unwrap();
                 x
             })
@@ -1459,6 +5196,18 @@ mod tests {
             })
     }
 
+    fn submit_and_wait_inner(c: Client) -> impl Future<Item = (), Error = error::CmdError> {
+        c.goto("https://www.wikipedia.org/")
+            .and_then(|mut c| c.form(Locator::Css("#search-form")))
+            .and_then(|mut f| f.set(Locator::Css("input[name='search']"), "foobar"))
+            .and_then(|f| f.submit_and_wait(Duration::from_secs(10)))
+            .and_then(|mut c| c.current_url())
+            .and_then(|url| {
+                assert_eq!(url.as_ref(), "https://en.wikipedia.org/wiki/Foobar");
+                Ok(())
+            })
+    }
+
     fn clicks_inner(c: Client) -> impl Future<Item = (), Error = error::CmdError> {
         // go to the Wikipedia frontpage this time
         c.goto("https://www.wikipedia.org/")
@@ -1546,6 +5295,18 @@ mod tests {
             })
     }
 
+    fn viewport_size_inner(c: Client) -> impl Future<Item = (), Error = error::CmdError> {
+        c.goto("https://www.wikipedia.org/")
+            .and_then(|mut c| c.set_window_size(800, 600).map(move |_| c))
+            .and_then(|mut c| c.get_window_size().map(move |outer| (c, outer)))
+            .and_then(|(mut c, outer)| c.viewport_size().map(move |inner| (outer, inner)))
+            .and_then(|((outer_width, outer_height), (width, height))| {
+                assert!(width > 0 && width <= outer_width);
+                assert!(height > 0 && height <= outer_height);
+                Ok(())
+            })
+    }
+
     fn window_position_inner(c: Client) -> impl Future<Item = (), Error = error::CmdError> {
         c.goto("https://www.wikipedia.org/")
             .and_then(|mut c| c.set_window_size(200, 100).map(move |_| c))
@@ -1610,101 +5371,2523 @@ mod tests {
             .and_then(|mut c| c.persist())
     }
 
-    mod chrome {
-        use super::*;
+    fn navigate_post_inner(c: Client) -> impl Future<Item = (), Error = error::CmdError> {
+        use hyper::{Body, Response};
+
+        // echo the submitted form back to us
+        let url = spawn_http(|req| {
+            req.into_body().concat2().map(|body| {
+                let body = String::from_utf8_lossy(&body).into_owned();
+                Response::builder()
+                    .header(hyper::header::CONTENT_TYPE, "text/html")
+                    .body(Body::from(format!("<p id=echo>{}</p>", body)))
+                    .unwrap()
+            })
+        });
+        let post = url.clone();
+        c.goto(&url)
+            .and_then(|c| {
+                let fields = [("fantoccini", "posted-value")];
+                c.navigate_post("/post", &fields, Duration::from_secs(10))
+            })
+            .and_then(|mut c| c.current_url().map(move |url| (c, url)))
+            .and_then(move |(mut c, url)| {
+                assert_eq!(url.as_ref(), format!("{}post", post));
+                c.find(Locator::Id("echo"))
+            })
+            .and_then(|mut e| e.text())
+            .map(|text| assert_eq!(text, "fantoccini=posted-value"))
+    }
 
-        #[test]
-        fn it_works() {
-            tester!(works_inner, "chrome")
-        }
-        #[test]
-        fn it_clicks() {
-            tester!(clicks_inner, "chrome")
-        }
-        #[test]
-        fn it_clicks_by_locator() {
-            tester!(clicks_inner_by_locator, "chrome")
-        }
-        #[test]
-        fn it_sends_keys_and_clear_input() {
-            tester!(send_keys_and_clear_input_inner, "chrome")
-        }
-        #[test]
-        fn it_can_be_raw() {
-            tester!(raw_inner, "chrome")
-        }
-        #[test]
-        #[ignore]
-        fn it_can_get_and_set_window_size() {
-            tester!(window_size_inner, "chrome")
-        }
-        #[test]
-        #[ignore]
-        fn it_can_get_and_set_window_position() {
-            tester!(window_position_inner, "chrome")
-        }
-        #[test]
-        #[ignore]
-        fn it_can_get_and_set_window_rect() {
-            tester!(window_rect_inner, "chrome")
-        }
-        #[test]
-        fn it_finds_all() {
-            tester!(finds_all_inner, "chrome")
-        }
-        #[test]
-        #[ignore]
-        fn it_persists() {
-            tester!(persist_inner, "chrome")
-        }
+    /// Have `save` write to a fresh temporary file named after `name`, and read back what it
+    /// wrote. The file is removed again afterwards.
+    fn saved_file<F, FF>(
+        name: &str,
+        save: F,
+    ) -> impl Future<Item = Vec<u8>, Error = error::CmdError>
+    where
+        F: FnOnce(&std::path::Path) -> FF,
+        FF: IntoFuture<Item = (), Error = error::CmdError>,
+    {
+        let name = format!("fantoccini-{}-{}", std::process::id(), name);
+        let path = std::env::temp_dir().join(name);
+        save(&path).into_future().and_then(move |_| {
+            let contents = std::fs::read(&path).map_err(error::CmdError::Io);
+            let _ = std::fs::remove_file(&path);
+            contents
+        })
     }
 
-    mod firefox {
-        use super::*;
+    /// Read the width and height out of the IHDR chunk of a PNG image.
+    fn png_dimensions(png: &[u8]) -> (u32, u32) {
+        assert_eq!(&png[..8], b"\x89PNG\r\n\x1a\n");
+        let be = |b: &[u8]| b.iter().fold(0u32, |acc, &b| (acc << 8) | u32::from(b));
+        (be(&png[16..20]), be(&png[20..24]))
+    }
 
-        #[test]
-        fn it_works() {
-            tester!(works_inner, "firefox")
-        }
-        #[test]
-        fn it_clicks() {
-            tester!(clicks_inner, "firefox")
-        }
-        #[test]
-        fn it_clicks_by_locator() {
-            tester!(clicks_inner_by_locator, "firefox")
-        }
-        #[test]
-        fn it_sends_keys_and_clear_input() {
-            tester!(send_keys_and_clear_input_inner, "firefox")
-        }
-        #[test]
-        fn it_can_be_raw() {
-            tester!(raw_inner, "firefox")
-        }
-        #[test]
-        #[ignore]
-        fn it_can_get_and_set_window_size() {
-            tester!(window_size_inner, "firefox")
-        }
-        #[test]
-        #[ignore]
-        fn it_can_get_and_set_window_position() {
-            tester!(window_position_inner, "firefox")
-        }
-        #[test]
-        #[ignore]
-        fn it_can_get_and_set_window_rect() {
-            tester!(window_rect_inner, "firefox")
-        }
-        #[test]
-        fn it_finds_all() {
-            tester!(finds_all_inner, "firefox")
-        }
-        #[test]
-        #[ignore]
-        fn it_persists() {
-            tester!(persist_inner, "firefox")
+    fn element_save_screenshot_inner(c: Client) -> impl Future<Item = (), Error = error::CmdError> {
+        c.goto("https://www.wikipedia.org/")
+            .and_then(|mut c| c.find(Locator::Css("img.central-featured-logo")))
+            .and_then(|mut e| saved_file("logo.png", move |path| e.save_screenshot(path)))
+            .map(|png| {
+                let (width, height) = png_dimensions(&png);
+                assert!(width > 0 && height > 0);
+            })
+    }
+
+    fn element_screenshot_full_inner(c: Client) -> impl Future<Item = (), Error = error::CmdError> {
+        c.goto("https://en.wikipedia.org/wiki/Foobar")
+            .and_then(|mut c| {
+                c.execute("return window.innerHeight", vec![])
+                    .map(move |h| (c, h.as_u64().expect("innerHeight should be a number")))
+            })
+            .and_then(|(mut c, viewport_height)| {
+                // the article body is much taller than the viewport
+                c.find(Locator::Id("content")).map(move |e| (e, viewport_height))
+            })
+            .and_then(|(mut e, viewport_height)| {
+                e.screenshot_full().map(move |png| (png, viewport_height))
+            })
+            .and_then(|(png, viewport_height)| {
+                let (_, height) = png_dimensions(&png);
+                assert!(u64::from(height) > viewport_height);
+                Ok(())
+            })
+    }
+
+    fn screenshot_opts_inner(c: Client) -> impl Future<Item = (), Error = error::CmdError> {
+        // only the top 100 pixels of the element start out in the viewport
+        let page = "data:text/html,<body style='margin: 0'><div id=s></div>\
+                    <div id=d style='width: 200px; height: 200px; background: red'></div>\
+                    <script>s.style.height = (innerHeight - 100) + 'px'</script>";
+        c.goto(page)
+            .and_then(|mut c| c.find(Locator::Id("d")).map(move |e| (c, e)))
+            .and_then(|(c, mut e)| {
+                e.screenshot_opts(ScreenshotOptions { scroll: false })
+                    .map(move |png| (c, e, png_dimensions(&png)))
+            })
+            .and_then(|(mut c, e, unscrolled)| {
+                c.execute("return window.pageYOffset", vec![])
+                    .map(move |offset| (e, unscrolled, offset))
+            })
+            .and_then(|(mut e, unscrolled, offset)| {
+                assert_eq!(offset.as_f64(), Some(0.0), "the page should not have scrolled");
+                e.screenshot_opts(ScreenshotOptions::default())
+                    .map(move |png| (unscrolled, png_dimensions(&png)))
+            })
+            .and_then(|(unscrolled, scrolled)| {
+                assert_eq!(unscrolled.0, scrolled.0);
+                assert!(unscrolled.1 < scrolled.1);
+                Ok(())
+            })
+    }
+
+    fn goto_screenshot_inner(mut c: Client) -> impl Future<Item = (), Error = error::CmdError> {
+        c.goto_screenshot("https://www.wikipedia.org/")
+            .and_then(move |png| {
+                assert_eq!(&png[..8], b"\x89PNG\r\n\x1a\n");
+                let (width, height) = png_dimensions(&png);
+                assert!(width > 0 && height > 0);
+                c.current_url()
+            })
+            .and_then(|url| {
+                assert_eq!(url.as_ref(), "https://www.wikipedia.org/");
+                Ok(())
+            })
+    }
+
+    fn save_screenshot_inner(c: Client) -> impl Future<Item = (), Error = error::CmdError> {
+        c.goto("https://www.wikipedia.org/")
+            .and_then(|mut c| saved_file("page.png", move |path| c.save_screenshot(path)))
+            .map(|png| {
+                let (width, height) = png_dimensions(&png);
+                assert!(width > 0 && height > 0);
+            })
+    }
+
+    fn screenshot_full_page_inner(c: Client) -> impl Future<Item = (), Error = error::CmdError> {
+        c.goto("https://en.wikipedia.org/wiki/Foobar")
+            .and_then(|mut c| {
+                c.execute("return window.innerHeight", vec![])
+                    .map(move |h| (c, h.as_u64().expect("innerHeight should be a number")))
+            })
+            .and_then(|(mut c, viewport_height)| {
+                c.screenshot_full_page().map(move |png| (png, viewport_height))
+            })
+            .and_then(|(png, viewport_height)| {
+                let (_, height) = png_dimensions(&png);
+                assert!(u64::from(height) > viewport_height);
+                Ok(())
+            })
+    }
+
+    fn find_by_escaped_id_inner(c: Client) -> impl Future<Item = (), Error = error::CmdError> {
+        c.goto(r#"data:text/html,<p id="foo:bar.baz">escaped</p><p id="foo">plain</p>"#)
+            .and_then(|mut c| c.find(Locator::Id("foo:bar.baz")))
+            .and_then(|mut e| e.text())
+            .and_then(|text| {
+                assert_eq!(text, "escaped");
+                Ok(())
+            })
+    }
+
+    fn download_to_writer_inner(c: Client) -> impl Future<Item = (), Error = error::CmdError> {
+        c.goto("https://www.wikipedia.org/")
+            .and_then(|mut c| c.find(Locator::Css("img.central-featured-logo")))
+            .and_then(|mut img| {
+                img.attr("src")
+                    .map(move |src| (img.client(), src.expect("image should have a src")))
+            })
+            .and_then(|(c, src)| {
+                // find out how large the image is supposed to be
+                c.clone()
+                    .raw_client_for(Method::HEAD, &src)
+                    .map(move |head| (c, src, head))
+            })
+            .and_then(|(c, src, head)| {
+                let expected = head
+                    .headers()
+                    .get(hyper::header::CONTENT_LENGTH)
+                    .and_then(|len| len.to_str().ok()?.parse::<u64>().ok())
+                    .expect("image should have a Content-Length");
+                c.download_to_writer(Method::GET, &src, std::io::Cursor::new(Vec::new()))
+                    .map(move |(writer, written)| (writer.into_inner(), written, expected))
+            })
+            .and_then(|(bytes, written, expected)| {
+                assert_eq!(written, expected);
+                assert_eq!(bytes.len() as u64, expected);
+                Ok(())
+            })
+    }
+
+    fn frames_inner(c: Client) -> impl Future<Item = (), Error = error::CmdError> {
+        c.goto(
+            r#"data:text/html,<p id="top">top</p><iframe srcdoc="<p id='inner'>inner</p>"></iframe>"#,
+        )
+        .and_then(|c| c.enter_frame(Some(0)))
+        .and_then(|mut c| c.find(Locator::Id("inner")))
+        .and_then(|mut e| e.text().map(move |text| (e.client(), text)))
+        .and_then(|(c, text)| {
+            assert_eq!(text, "inner");
+            c.switch_to_default_content()
+        })
+        .and_then(|mut c| c.find(Locator::Id("top")))
+        .and_then(|mut e| e.text())
+        .and_then(|text| {
+            assert_eq!(text, "top");
+            Ok(())
+        })
+    }
+
+    fn stale_inner(c: Client) -> impl Future<Item = (), Error = error::CmdError> {
+        c.goto("https://en.wikipedia.org/wiki/Foobar")
+            .and_then(|mut c| c.find(Locator::Css("#content")))
+            .and_then(|mut e| e.is_stale().map(move |stale| (e, stale)))
+            .and_then(|(e, stale)| {
+                assert!(!stale);
+                e.clone()
+                    .client()
+                    .goto("https://en.wikipedia.org/wiki/Foo_Lake")
+                    .map(move |_| e)
+            })
+            .and_then(|mut e| e.is_stale())
+            .and_then(|stale| {
+                assert!(stale);
+                Ok(())
+            })
+    }
+
+    fn download_directory_inner(c: Client) -> impl Future<Item = (), Error = error::CmdError> {
+        let dir = std::env::temp_dir().join(format!("fantoccini-downloads-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let file = dir.join("hello.txt");
+
+        let cleanup = dir.clone();
+        c.goto("data:text/html,<a id=dl href='data:text/plain,hello' download='hello.txt'>get</a>")
+            .and_then(move |mut c| c.set_download_directory(&dir).map(move |_| c))
+            .and_then(|mut c| c.find(Locator::Id("dl")))
+            .and_then(|e| e.click())
+            .and_then(move |_| {
+                // the download finishes in the background
+                let wait = future::loop_fn(file, |file| {
+                    sleep(POLL_INTERVAL).map(move |_| {
+                        if file.exists() {
+                            future::Loop::Break(())
+                        } else {
+                            future::Loop::Continue(file)
+                        }
+                    })
+                });
+                wait_with_timeout(wait, Duration::from_secs(10))
+            })
+            .then(move |r| {
+                let _ = std::fs::remove_dir_all(&cleanup);
+                r
+            })
+    }
+
+    fn wait_for_download_inner(c: Client) -> impl Future<Item = (), Error = error::CmdError> {
+        let dir = std::env::temp_dir().join(format!("fantoccini-wait-dl-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+
+        c.goto("data:text/html,<a id=dl href='data:text/plain,hello' download='hello.txt'>get</a>")
+            .and_then(move |mut c| {
+                c.set_download_directory(&dir)
+                    .map(move |_| (c, dir))
+            })
+            .and_then(|(mut c, dir)| {
+                let download = c.wait_for_download(&dir, Duration::from_secs(10));
+                c.find(Locator::Id("dl"))
+                    .and_then(|e| e.click())
+                    .and_then(|_| download)
+            })
+            .and_then(|path| {
+                assert_eq!(path.file_name().unwrap(), "hello.txt");
+                assert_eq!(std::fs::read_to_string(&path).unwrap(), "hello");
+                let _ = std::fs::remove_file(&path);
+                Ok(())
+            })
+    }
+
+    fn element_from_point_inner(c: Client) -> impl Future<Item = (), Error = error::CmdError> {
+        c.goto("data:text/html,<div style='height:100px'>top</div><button id=b>target</button>")
+            .and_then(|mut c| {
+                let center = "var r = document.getElementById('b').getBoundingClientRect();\
+                              return [r.left + r.width / 2, r.top + r.height / 2]";
+                c.execute(center, vec![]).map(move |center| (c, center))
+            })
+            .and_then(|(mut c, center)| {
+                let x = center[0].as_f64().unwrap();
+                let y = center[1].as_f64().unwrap();
+                c.element_from_point(x, y).map(move |e| (c, e))
+            })
+            .and_then(|(mut c, mut e)| {
+                e.attr("id").and_then(move |id| {
+                    assert_eq!(id.as_ref().map(String::as_str), Some("b"));
+                    // nothing lies outside of the viewport
+                    c.element_from_point(-10.0, -10.0).then(|r| match r {
+                        Err(ref e) if e.is_miss() => Ok(()),
+                        Err(e) => Err(e),
+                        Ok(_) => panic!("found an element outside of the viewport"),
+                    })
+                })
+            })
+    }
+
+    fn scroll_inner(c: Client) -> impl Future<Item = (), Error = error::CmdError> {
+        let offset = "return window.pageYOffset";
+        c.goto("data:text/html,<div style='height:10000px'>tall</div>")
+            .and_then(|mut c| c.scroll_to(0.0, 500.0).map(move |_| c))
+            .and_then(move |mut c| c.execute(offset, vec![]).map(move |y| (c, y)))
+            .and_then(|(mut c, y)| {
+                assert_eq!(y.as_f64(), Some(500.0));
+                c.scroll_by(0.0, 250.0).map(move |_| c)
+            })
+            .and_then(move |mut c| c.execute(offset, vec![]))
+            .and_then(|y| {
+                assert_eq!(y.as_f64(), Some(750.0));
+                Ok(())
+            })
+    }
+
+    fn set_permission_inner(c: Client) -> impl Future<Item = (), Error = error::CmdError> {
+        use hyper::{Body, Response};
+
+        // loopback origins are secure contexts, so they may ask for the location
+        let url = spawn_http(|_| Ok::<_, hyper::Error>(Response::new(Body::from("where am i?"))));
+        let script = "\
+                      var done = arguments[0];\
+                      navigator.geolocation.getCurrentPosition(\
+                        function(p) { done([p.coords.latitude, p.coords.longitude]); },\
+                        function(e) { done(e.message); })";
+        c.goto(&url)
+            .and_then(|mut c| {
+                let params = serde_json::json!({
+                    "latitude": 52.5,
+                    "longitude": 13.4,
+                    "accuracy": 1,
+                });
+                c.cdp("Emulation.setGeolocationOverride", params)
+                    .map(move |_| c)
+            })
+            .and_then(|mut c| {
+                c.set_permission("geolocation", PermissionState::Granted)
+                    .map(move |_| c)
+            })
+            .and_then(move |mut c| {
+                // were we to be prompted, the script would never call back
+                c.execute_async_timeout(script, vec![], Duration::from_secs(10))
+            })
+            .map(|position| assert_eq!(position, serde_json::json!([52.5, 13.4])))
+    }
+
+    fn fetch_inner(c: Client) -> impl Future<Item = (), Error = error::CmdError> {
+        c.goto("https://www.wikipedia.org/")
+            .and_then(|c| c.fetch(Method::GET, "https://en.wikipedia.org/wiki/Foobar"))
+            .and_then(|rsp| {
+                assert!(rsp.status().is_success());
+                rsp.text()
+            })
+            .and_then(|text| {
+                assert!(!text.is_empty());
+                Ok(())
+            })
+    }
+
+    fn wait_for_clickable_inner(c: Client) -> impl Future<Item = (), Error = error::CmdError> {
+        c.goto(
+            "data:text/html,<button id=b disabled onclick=\"this.textContent='clicked'\">wait</button>\
+             <script>setTimeout(function() { document.getElementById('b').disabled = false; }, 1000)\
+             </script>",
+        )
+        .and_then(|mut c| {
+            // the button starts out disabled, so we should not find it clickable right away
+            c.find(Locator::Id("b"))
+                .and_then(|mut e| e.is_enabled())
+                .map(move |enabled| (c, enabled))
+        })
+        .and_then(|(c, enabled)| {
+            assert!(!enabled);
+            c.wait_for_clickable(Locator::Id("b"), Duration::from_secs(10))
+        })
+        .and_then(|mut e| e.is_enabled().map(move |enabled| (e, enabled)))
+        .and_then(|(e, enabled)| {
+            assert!(enabled);
+            e.clone().click().map(move |_| e)
+        })
+        .and_then(|mut e| e.text())
+        .and_then(|text| {
+            assert_eq!(text, "clicked");
+            Ok(())
+        })
+    }
+
+    fn close_window_and_switch_inner(c: Client) -> impl Future<Item = (), Error = error::CmdError> {
+        c.goto("https://en.wikipedia.org/")
+            .and_then(|mut c| c.window().map(move |original| (c, original)))
+            .and_then(|(mut c, original)| {
+                c.execute("window.open('https://en.wikipedia.org/wiki/Foobar')", vec![])
+                    .map(move |_| (c, original))
+            })
+            .and_then(|(mut c, original)| c.windows().map(move |all| (c, original, all)))
+            .and_then(|(mut c, original, all)| {
+                assert_eq!(all.len(), 2);
+                let tab = all.into_iter().find(|w| *w != original).unwrap();
+                c.switch_to_window(tab).map(move |_| (c, original))
+            })
+            .and_then(|(mut c, original)| {
+                c.close_window_and_switch()
+                    .map(move |focused| (c, original, focused))
+            })
+            .and_then(|(mut c, original, focused)| {
+                assert_eq!(focused, Some(original));
+                // no explicit switch needed
+                c.current_url()
+            })
+            .and_then(|url| {
+                assert_eq!(url.as_ref(), "https://en.wikipedia.org/wiki/Main_Page");
+                Ok(())
+            })
+    }
+
+    fn new_tab_inner(c: Client) -> impl Future<Item = (), Error = error::CmdError> {
+        c.goto("https://en.wikipedia.org/")
+            .and_then(|mut c| c.window().map(move |original| (c, original)))
+            .and_then(|(mut c, original)| c.new_tab().map(move |tab| (c, original, tab)))
+            .and_then(|(mut c, original, tab)| {
+                assert_ne!(tab, original);
+                c.window().map(move |focused| (c, tab, focused))
+            })
+            .and_then(|(mut c, tab, focused)| {
+                assert_eq!(focused, tab);
+                c.current_url()
+            })
+            .and_then(|url| {
+                assert_eq!(url.as_ref(), "about:blank");
+                Ok(())
+            })
+    }
+
+    fn click_at_inner(c: Client) -> impl Future<Item = (), Error = error::CmdError> {
+        c.goto(
+            "data:text/html,<canvas id=c width=200 height=100 style=\"border:0\" \
+             onclick=\"window.clicked = [event.offsetX, event.offsetY]\"></canvas>",
+        )
+        .and_then(|mut c| c.find(Locator::Id("c")))
+        .and_then(|mut e| e.click_at(30.0, 40.0).map(move |_| e.client()))
+        .and_then(|mut c| c.execute("return window.clicked", vec![]))
+        .and_then(|clicked| {
+            let clicked = clicked.as_array().expect("canvas was not clicked");
+            let x = clicked[0].as_f64().unwrap();
+            let y = clicked[1].as_f64().unwrap();
+            assert!((x - 30.0).abs() <= 1.0, "clicked at x = {}", x);
+            assert!((y - 40.0).abs() <= 1.0, "clicked at y = {}", y);
+            Ok(())
+        })
+    }
+
+    fn move_with_offset_inner(c: Client) -> impl Future<Item = (), Error = error::CmdError> {
+        c.goto(
+            "data:text/html,<canvas id=c width=200 height=100 style=\"border:0\" \
+             onclick=\"window.clicked = [event.offsetX, event.offsetY]\"></canvas>",
+        )
+        .and_then(|mut c| c.find(Locator::Id("c")).map(move |e| (c, e)))
+        .and_then(|(mut c, e)| {
+            let actions = ActionChain::new()
+                .move_to_element_with_offset(&e, 150.0, 20.0)
+                .click();
+            c.perform_actions(actions).map(move |_| c)
+        })
+        .and_then(|mut c| c.execute("return window.clicked", vec![]))
+        .and_then(|clicked| {
+            let clicked = clicked.as_array().expect("canvas was not clicked");
+            let x = clicked[0].as_f64().unwrap();
+            let y = clicked[1].as_f64().unwrap();
+            assert!((x - 150.0).abs() <= 1.0, "clicked at x = {}", x);
+            assert!((y - 20.0).abs() <= 1.0, "clicked at y = {}", y);
+            Ok(())
+        })
+    }
+
+    fn hover_inner(c: Client) -> impl Future<Item = (), Error = error::CmdError> {
+        c.goto(
+            "data:text/html,<div id=menu style=\"width:200px;height:50px\" \
+             onmouseover=\"document.getElementById('sub').style.display='block'\">menu</div>\
+             <div id=sub style=\"display:none\">submenu</div>",
+        )
+        .and_then(|mut c| {
+            c.find(Locator::Id("sub"))
+                .and_then(|e| e.displayed())
+                .map(move |shown| (c, shown))
+        })
+        .and_then(|(mut c, shown)| {
+            assert!(!shown);
+            c.find(Locator::Id("menu"))
+                .and_then(|mut e| e.hover())
+                .map(move |_| c)
+        })
+        .and_then(|mut c| c.find(Locator::Id("sub")))
+        .and_then(|e| e.displayed())
+        .and_then(|shown| {
+            assert!(shown);
+            Ok(())
+        })
+    }
+
+    fn action_pause_inner(c: Client) -> impl Future<Item = (), Error = error::CmdError> {
+        c.goto(
+            "data:text/html,<button id=b onclick=\"clearTimeout(window.t); \
+             window.t = setTimeout(function() { window.fired = (window.fired || 0) + 1; }, 200)\">\
+             debounced</button>",
+        )
+        .and_then(|mut c| c.find(Locator::Id("b")).map(move |e| (c, e)))
+        .and_then(|(mut c, e)| {
+            // without the pause, the second click would cancel the first one's handler
+            let actions = ActionChain::new()
+                .move_to_element(&e)
+                .click()
+                .pause(Duration::from_millis(500))
+                .click();
+            c.perform_actions(actions).map(move |_| c)
+        })
+        .and_then(|c| sleep(Duration::from_secs(1)).map(move |_| c))
+        .and_then(|mut c| c.execute("return window.fired", vec![]))
+        .and_then(|fired| {
+            assert_eq!(fired, Json::from(2));
+            Ok(())
+        })
+    }
+
+    fn execute_file_inner(mut c: Client) -> impl Future<Item = (), Error = error::CmdError> {
+        let dir = std::env::temp_dir();
+        let script = dir.join(format!("fantoccini-execute-file-{}.js", std::process::id()));
+        std::fs::write(&script, "return arguments[0] * 2;").unwrap();
+
+        let missing = dir.join("fantoccini-no-such-script.js");
+        c.execute_file(&missing, vec![])
+            .then(move |r| {
+                match r {
+                    Err(error::CmdError::Io(_)) => {}
+                    r => panic!("expected an i/o error for a missing file, got {:?}", r),
+                }
+                c.execute_file(&script, vec![Json::from(21)])
+                    .then(move |r| {
+                        let _ = std::fs::remove_file(&script);
+                        r
+                    })
+            })
+            .and_then(|v| {
+                assert_eq!(v, Json::from(42));
+                Ok(())
+            })
+    }
+
+    fn finds_by_tag_name_inner(c: Client) -> impl Future<Item = (), Error = error::CmdError> {
+        c.goto("https://en.wikipedia.org/")
+            .and_then(|mut c| c.find_all(Locator::TagName("a")))
+            .and_then(|links| {
+                // the front page has plenty of links, in the navigation alone
+                assert!(links.len() > 50, "only found {} links", links.len());
+                Ok(())
+            })
+    }
+
+    fn finds_by_class_name_inner(c: Client) -> impl Future<Item = (), Error = error::CmdError> {
+        c.goto("data:text/html,<p class=other>no</p><p class=\"lead intro\">hello</p>")
+            .and_then(|mut c| {
+                c.find(Locator::ClassName("lead"))
+                    .and_then(|mut e| e.text())
+                    .map(move |text| (c, text))
+            })
+            .and_then(|(mut c, text)| {
+                assert_eq!(text, "hello");
+                c.find(Locator::ClassName("lead intro")).then(|r| match r {
+                    Err(error::CmdError::InvalidArgument(..)) => Ok(()),
+                    Err(e) => panic!("unexpected error: {:?}", e),
+                    Ok(_) => panic!("multiple class names should be rejected"),
+                })
+            })
+    }
+
+    fn deadline_inner(mut c: Client) -> impl Future<Item = (), Error = error::CmdError> {
+        let slow = "var start = Date.now(); while (Date.now() - start < 3000) {} return 1;";
+        with_deadline(c.execute(slow, vec![]), Duration::from_millis(500))
+            .then(move |r| {
+                match r {
+                    Err(error::CmdError::Timeout) => {}
+                    r => panic!("expected the command to time out, got {:?}", r),
+                }
+                // the client remains usable once the browser catches up
+                with_deadline(c.execute("return 2;", vec![]), Duration::from_secs(30))
+            })
+            .and_then(|v| {
+                assert_eq!(v, Json::from(2));
+                Ok(())
+            })
+    }
+
+    fn insecure_certs_inner(c: Client) -> impl Future<Item = (), Error = error::CmdError> {
+        // this page is served with a self-signed certificate
+        c.goto("https://self-signed.badssl.com/")
+            .and_then(|mut c| c.find(Locator::Css("#content h1")))
+            .and_then(|mut e| e.text())
+            .and_then(|text| {
+                // without the flag, we would be looking at the browser's certificate warning
+                assert!(text.contains("self-signed"), "unexpected page heading {:?}", text);
+                Ok(())
+            })
+    }
+
+    /// Spawn a minimal stand-in for an HTTP proxy, and make the browser use it.
+    ///
+    /// Rather than forwarding requests, the proxy answers every request itself with the request
+    /// target it was given, so tests can tell which requests went through it.
+    fn use_stub_proxy(b: &mut ClientBuilder) {
+        use hyper::{Body, Response};
+
+        let url = spawn_http(|req| {
+            Ok::<_, hyper::Error>(Response::new(Body::from(req.uri().to_string())))
+        });
+        let addr = url.trim_start_matches("http://").trim_end_matches('/');
+
+        let mut caps = b.capabilities.clone();
+        caps.insert(
+            "proxy".to_string(),
+            serde_json::json!({ "proxyType": "manual", "httpProxy": addr.to_string() }),
+        );
+        b.capabilities(caps);
+    }
+
+    fn proxy_raw_requests_inner(mut c: Client) -> impl Future<Item = (), Error = error::CmdError> {
+        c.proxy_raw_requests(true);
+        // this host does not exist, so only the proxy can answer for it
+        c.goto("http://fantoccini.invalid/")
+            .and_then(|c| c.fetch(Method::GET, "/raw"))
+            .and_then(|rsp| rsp.text())
+            .and_then(|text| {
+                assert_eq!(text, "http://fantoccini.invalid/raw");
+                Ok(())
+            })
+    }
+
+    /// Write a Firefox profile that overrides the user agent, and return its directory.
+    fn write_test_profile() -> std::path::PathBuf {
+        let dir = std::env::temp_dir().join(format!("fantoccini-profile-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        std::fs::write(
+            dir.join("user.js"),
+            "user_pref(\"general.useragent.override\", \"fantoccini-profile-test\");\n",
+        )
+        .unwrap();
+        dir
+    }
+
+    fn firefox_profile_inner(c: Client) -> impl Future<Item = (), Error = error::CmdError> {
+        c.goto("data:text/html,profile")
+            .and_then(|mut c| c.execute("return navigator.userAgent", vec![]))
+            .and_then(|ua| {
+                assert_eq!(ua, Json::from("fantoccini-profile-test"));
+                Ok(())
+            })
+    }
+
+    fn addon_inner(mut c: Client) -> impl Future<Item = (), Error = error::CmdError> {
+        let dir = std::env::temp_dir().join(format!("fantoccini-addon-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        std::fs::write(
+            dir.join("manifest.json"),
+            r#"{
+                "manifest_version": 2,
+                "name": "fantoccini test add-on",
+                "version": "1.0",
+                "applications": { "gecko": { "id": "test-addon@fantoccini" } }
+            }"#,
+        )
+        .unwrap();
+
+        let mut c2 = c.clone();
+        c.install_addon(&dir, true)
+            .and_then(move |id| {
+                assert!(!id.is_empty());
+                c2.uninstall_addon(&id)
+            })
+            .then(move |r| {
+                let _ = std::fs::remove_dir_all(&dir);
+                r
+            })
+    }
+
+    fn context_inner(c: Client) -> impl Future<Item = (), Error = error::CmdError> {
+        c.goto("https://www.wikipedia.org/")
+            .and_then(|mut c| c.set_context(Context::Chrome).map(move |_| c))
+            .and_then(|mut c| {
+                // only privileged scripts can see the browser's own services
+                c.execute("return Services.appinfo.name", vec![])
+                    .map(move |name| (c, name))
+            })
+            .and_then(|(mut c, name)| {
+                assert_eq!(name, Json::from("Firefox"));
+                c.set_context(Context::Content).map(move |_| c)
+            })
+            .and_then(|mut c| c.context().map(move |ctx| (c, ctx)))
+            .and_then(|(mut c, ctx)| {
+                assert_eq!(ctx, Context::Content);
+                c.current_url()
+            })
+            .and_then(|url| {
+                assert_eq!(url.as_ref(), "https://www.wikipedia.org/");
+                Ok(())
+            })
+    }
+
+    fn aria_inner(c: Client) -> impl Future<Item = (), Error = error::CmdError> {
+        c.goto("data:text/html,<button id=b aria-label=Close>x</button>")
+            .and_then(|mut c| c.find(Locator::Id("b")))
+            .and_then(|mut e| e.aria_role().map(move |role| (e, role)))
+            .and_then(|(mut e, role)| {
+                assert_eq!(role, "button");
+                e.aria_label()
+            })
+            .and_then(|label| {
+                assert_eq!(label, "Close");
+                Ok(())
+            })
+    }
+
+    fn retry_stale_inner(c: Client) -> impl Future<Item = (), Error = error::CmdError> {
+        c.goto(
+            "data:text/html,<div id=w><button id=b onclick=\"document.title='clicked'\">go</button></div>",
+        )
+        .and_then(|mut c| c.find(Locator::Id("b")).map(move |e| (c, e)))
+        .and_then(|(mut c, e)| {
+            // re-render the button, which leaves our handle to it stale
+            let replace = "var w = document.getElementById('w'); w.innerHTML = w.innerHTML;";
+            c.execute(replace, vec![]).map(move |_| (c, e))
+        })
+        .and_then(|(c, mut e)| e.is_stale().map(move |stale| (c, e, stale)))
+        .and_then(|(mut c, e, stale)| {
+            assert!(stale);
+            c.retry_stale_elements(true);
+            e.click()
+        })
+        .and_then(|mut c| c.execute("return document.title;", vec![]))
+        .and_then(|title| {
+            assert_eq!(title, Json::from("clicked"));
+            Ok(())
+        })
+    }
+
+    fn attach_inner(
+        c: Client,
+        webdriver: &'static str,
+    ) -> impl Future<Item = (), Error = error::CmdError> {
+        c.goto("https://www.wikipedia.org/")
+            .and_then(|mut c| c.session_id().map(move |id| (c, id)))
+            .and_then(|(c, id)| {
+                let id = id.expect("session should have an id");
+                Client::attach(webdriver, id, Json::Null)
+                    .map(move |other| (c, other))
+                    .map_err(|e| panic!("failed to attach to session: {}", e))
+            })
+            .and_then(|(c, mut other)| {
+                // the session belongs to the test harness, so don't close it when we're done
+                other
+                    .persist()
+                    .and_then(move |_| other.current_url())
+                    .map(move |url| (c, url))
+            })
+            .and_then(|(mut c, url)| c.current_url().map(move |mine| (url, mine)))
+            .and_then(|(url, mine)| {
+                assert_eq!(url, mine);
+                assert_eq!(url.as_ref(), "https://www.wikipedia.org/");
+                Ok(())
+            })
+    }
+
+    fn clear_via_keyboard_inner(c: Client) -> impl Future<Item = (), Error = error::CmdError> {
+        c.goto("data:text/html,<input id=i>")
+            .and_then(|mut c| c.find(Locator::Id("i")))
+            .and_then(|mut e| e.send_keys("residual text").map(move |_| e))
+            .and_then(|mut e| e.clear_via_keyboard().map(move |_| e))
+            .and_then(|mut e| e.prop("value"))
+            .and_then(|value| {
+                assert_eq!(value.as_ref().map(String::as_str), Some(""));
+                Ok(())
+            })
+    }
+
+    fn select_all_inner(c: Client) -> impl Future<Item = (), Error = error::CmdError> {
+        c.goto("data:text/html,<input id=i value='old text'>")
+            .and_then(|mut c| c.find(Locator::Id("i")))
+            .and_then(|mut e| e.select_all().map(move |_| e))
+            .and_then(|e| {
+                let mut c = e.client();
+                c.perform_actions(ActionChain::new().send_keys("new"))
+                    .map(move |_| c)
+            })
+            .and_then(|mut c| c.find(Locator::Id("i")))
+            .and_then(|mut e| e.prop("value"))
+            .and_then(|value| {
+                assert_eq!(value.as_ref().map(String::as_str), Some("new"));
+                Ok(())
+            })
+    }
+
+    fn goto_wait_stable_inner(c: Client) -> impl Future<Item = (), Error = error::CmdError> {
+        // the page moves on twice shortly after loading
+        let page = "data:text/html,<script>\
+                    setTimeout(function() { location.hash = 'first'; }, 200);\
+                    setTimeout(function() { location.hash = 'second'; }, 400);\
+                    </script>";
+        c.goto_wait_stable(page, Duration::from_secs(1), Duration::from_secs(30))
+            .and_then(|mut c| c.current_url())
+            .and_then(|url| {
+                assert_eq!(url.fragment(), Some("second"));
+                Ok(())
+            })
+    }
+
+    fn cookies_for_domain_inner(c: Client) -> impl Future<Item = (), Error = error::CmdError> {
+        c.goto("https://en.wikipedia.org/")
+            .and_then(|mut c| {
+                // one cookie shared with all of wikipedia.org, and one just for this subdomain
+                let set = "document.cookie = 'shared=1; domain=.wikipedia.org; path=/';\
+                           document.cookie = 'local=2; path=/';";
+                c.execute(set, vec![]).map(move |_| c)
+            })
+            .and_then(|mut c| c.get_cookies_for_domain("en.wikipedia.org").map(move |cs| (c, cs)))
+            .and_then(|(mut c, cookies)| {
+                let names: Vec<_> = cookies.iter().map(|c| c.name().to_string()).collect();
+                assert!(names.contains(&"local".to_string()), "cookies: {:?}", names);
+                assert!(!names.contains(&"shared".to_string()), "cookies: {:?}", names);
+                c.get_cookies_for_domain("wikipedia.org")
+            })
+            .and_then(|cookies| {
+                let names: Vec<_> = cookies.iter().map(|c| c.name().to_string()).collect();
+                assert!(names.contains(&"local".to_string()), "cookies: {:?}", names);
+                assert!(names.contains(&"shared".to_string()), "cookies: {:?}", names);
+                Ok(())
+            })
+    }
+
+    fn command_observer_inner(mut c: Client) -> impl Future<Item = (), Error = error::CmdError> {
+        use std::sync::{Arc, Mutex};
+
+        let seen = Arc::new(Mutex::new(Vec::new()));
+        let log = seen.clone();
+        c.set_command_observer(move |endpoint, took| {
+            log.lock().unwrap().push((endpoint.to_string(), took));
+        })
+        .and_then(move |_| c.goto("https://www.wikipedia.org/"))
+        .and_then(|mut c| c.find(Locator::Css("#searchInput")))
+        .and_then(move |_| {
+            let seen = seen.lock().unwrap();
+            let find = seen.iter().find(|&&(ref endpoint, _)| endpoint == "element");
+            let &(_, took) = find.expect("find should have been observed");
+            assert!(took > Duration::from_secs(0));
+            Ok(())
+        })
+    }
+
+    fn execute_elements_inner(c: Client) -> impl Future<Item = (), Error = error::CmdError> {
+        c.goto("https://en.wikipedia.org/")
+            .and_then(|mut c| {
+                let script = "return Array.from(document.querySelectorAll('#p-interaction a'));";
+                c.execute_elements(script, vec![]).map(move |links| (c, links))
+            })
+            .and_then(|(mut c, links)| {
+                assert!(!links.is_empty());
+                let first = links.into_iter().next().unwrap();
+                c.execute_elements("return [1];", vec![]).then(move |r| {
+                    match r {
+                        Err(error::CmdError::NotW3C(_)) => {}
+                        Err(e) => panic!("unexpected error: {:?}", e),
+                        Ok(_) => panic!("a number is not an element"),
+                    }
+                    first.click()
+                })
+            })
+            .and_then(|mut c| c.current_url())
+            .and_then(|url| {
+                assert_eq!(url.as_ref(), "https://en.wikipedia.org/wiki/Help:Contents");
+                Ok(())
+            })
+    }
+
+    fn local_storage_inner(c: Client) -> impl Future<Item = (), Error = error::CmdError> {
+        c.goto("https://www.wikipedia.org/")
+            .and_then(|mut c| c.set_local_storage_item("greeting", "hello").map(move |_| c))
+            .and_then(|mut c| c.get_local_storage_item("greeting").map(move |v| (c, v)))
+            .and_then(|(mut c, v)| {
+                assert_eq!(v, Some("hello".to_string()));
+                c.clear_local_storage().map(move |_| c)
+            })
+            .and_then(|mut c| c.get_local_storage_item("greeting"))
+            .and_then(|v| {
+                assert_eq!(v, None);
+                Ok(())
+            })
+    }
+
+    fn session_storage_inner(c: Client) -> impl Future<Item = (), Error = error::CmdError> {
+        c.goto("https://www.wikipedia.org/")
+            .and_then(|mut c| c.set_session_storage_item("step", "2").map(move |_| c))
+            .and_then(|mut c| {
+                // session storage is separate from local storage
+                c.get_local_storage_item("step").map(move |v| (c, v))
+            })
+            .and_then(|(mut c, v)| {
+                assert_eq!(v, None);
+                c.get_session_storage_item("step").map(move |v| (c, v))
+            })
+            .and_then(|(mut c, v)| {
+                assert_eq!(v, Some("2".to_string()));
+                c.clear_session_storage().map(move |_| c)
+            })
+            .and_then(|mut c| c.get_session_storage_item("step"))
+            .and_then(|v| {
+                assert_eq!(v, None);
+                Ok(())
+            })
+    }
+
+    fn clear_browser_state_inner(c: Client) -> impl Future<Item = (), Error = error::CmdError> {
+        c.goto("https://www.wikipedia.org/")
+            .and_then(|mut c| {
+                c.execute("document.cookie = 'fantoccini=1; path=/';", vec![])
+                    .map(move |_| c)
+            })
+            .and_then(|mut c| c.set_local_storage_item("local", "1").map(move |_| c))
+            .and_then(|mut c| c.set_session_storage_item("session", "1").map(move |_| c))
+            .and_then(|mut c| c.clear_browser_state().map(move |_| c))
+            .and_then(|mut c| c.get_all_cookies().map(move |cookies| (c, cookies)))
+            .and_then(|(mut c, cookies)| {
+                assert!(cookies.is_empty(), "cookies left: {:?}", cookies);
+                c.get_local_storage_item("local").map(move |v| (c, v))
+            })
+            .and_then(|(mut c, v)| {
+                assert_eq!(v, None);
+                c.get_session_storage_item("session")
+            })
+            .and_then(|v| {
+                assert_eq!(v, None);
+                Ok(())
+            })
+    }
+
+    fn wait_for_url_change_inner(c: Client) -> impl Future<Item = (), Error = error::CmdError> {
+        c.goto(
+            "data:text/html,<a id=go href='javascript:void(0)' onclick=\"setTimeout(function() { \
+             location.href = 'https://www.wikipedia.org/'; }, 500)\">go</a>",
+        )
+        .and_then(|mut c| c.current_url().map(move |start| (c, start)))
+        .and_then(|(mut c, start)| {
+            let change = c.wait_for_url_change(Duration::from_secs(10));
+            c.find(Locator::Id("go"))
+                .and_then(|e| e.click())
+                .and_then(|_| change)
+                .map(move |url| (start, url))
+        })
+        .and_then(|(start, url)| {
+            assert_ne!(url, start);
+            assert_eq!(url.as_ref(), "https://www.wikipedia.org/");
+            Ok(())
+        })
+    }
+
+    fn error_details_inner(c: Client) -> impl Future<Item = (), Error = error::CmdError> {
+        c.goto("data:text/html,errors")
+            .and_then(|mut c| c.execute("throw new Error('boom');", vec![]))
+            .then(|r| match r {
+                Err(error::CmdError::Standard(e)) => {
+                    assert_eq!(e.error, webdriver::error::ErrorStatus::JavascriptError);
+                    assert!(e.message.contains("boom"), "unexpected message {:?}", e.message);
+                    assert!(!e.stacktrace.is_empty());
+                    Ok(())
+                }
+                Err(e) => Err(e),
+                Ok(v) => panic!("script should have failed, but returned {:?}", v),
+            })
+    }
+
+    fn send_keys_chunked_inner(c: Client) -> impl Future<Item = (), Error = error::CmdError> {
+        // 100k characters, with emoji that take up two UTF-16 code units each
+        let text: String = std::iter::repeat("abc\u{1F600}").take(25_000).collect();
+        c.goto("data:text/html,<textarea id=t></textarea>")
+            .and_then(|mut c| c.find(Locator::Id("t")))
+            .and_then(move |mut e| {
+                // chunks of an odd size end between the halves of surrogate pairs in UTF-16
+                e.send_keys_chunked(&text, 999).map(move |_| (e, text))
+            })
+            .and_then(|(mut e, text)| e.prop("value").map(move |value| (value, text)))
+            .and_then(|(value, text)| {
+                assert!(value.as_ref() == Some(&text), "text was mangled");
+                Ok(())
+            })
+    }
+
+    fn props_for_inner(c: Client) -> impl Future<Item = (), Error = error::CmdError> {
+        c.goto("https://www.wikipedia.org/")
+            .and_then(|mut c| c.find_all(Locator::Css("a[href]")).map(move |links| (c, links)))
+            .and_then(|(mut c, links)| {
+                let refs: Vec<&Element> = links.iter().collect();
+                c.props_for(&refs, "href").map(move |hrefs| (links, hrefs))
+            })
+            .and_then(|(links, hrefs)| {
+                assert!(!links.is_empty());
+                assert_eq!(hrefs.len(), links.len());
+                assert!(hrefs.iter().all(|href| href.is_string()));
+                Ok(())
+            })
+    }
+
+    fn compiled_locator_inner(c: Client) -> impl Future<Item = (), Error = error::CmdError> {
+        let links = Locator::Css("li a").compile().unwrap();
+        c.goto("data:text/html,<ul><li><a href=#a>a</a><li><a href=#b>b</a></ul><p id=main>")
+            .and_then(|mut c| c.find_all(Locator::Css("li a")).map(move |expected| (c, expected)))
+            .and_then(move |(c, expected)| {
+                let expected: Vec<_> = expected.into_iter().map(|e| e.web_element()).collect();
+                assert_eq!(expected.len(), 2);
+                future::loop_fn((c, 0), move |(mut c, i)| {
+                    let expected = expected.clone();
+                    c.find_all_compiled(&links).map(move |found| {
+                        let found: Vec<_> = found.into_iter().map(|e| e.web_element()).collect();
+                        assert_eq!(found, expected);
+                        if i == 9 {
+                            future::Loop::Break(c)
+                        } else {
+                            future::Loop::Continue((c, i + 1))
+                        }
+                    })
+                })
+            })
+            .and_then(|mut c| {
+                let main = Locator::Id("main").compile().unwrap();
+                c.find_compiled(&main).map(move |e| (c, e))
+            })
+            .and_then(|(mut c, compiled)| {
+                c.find(Locator::Id("main")).map(move |e| (compiled, e))
+            })
+            .and_then(|(compiled, e)| {
+                assert_eq!(compiled.web_element(), e.web_element());
+                // invalid locators are caught when they are compiled
+                assert!(Locator::ClassName("foo bar").compile().is_err());
+                Ok(())
+            })
+    }
+
+    fn navigation_timing_inner(c: Client) -> impl Future<Item = (), Error = error::CmdError> {
+        c.goto("https://www.wikipedia.org/")
+            // the load event may still be running when the navigation completes
+            .and_then(|c| {
+                c.wait_for(|c| {
+                    c.navigation_timing()
+                        .map(|t| t.map(|t| t.load_event_end > 0.0).unwrap_or(false))
+                })
+            })
+            .and_then(|mut c| c.navigation_timing())
+            .and_then(|timing| {
+                let timing = timing.expect("page has no navigation entry");
+                assert!(timing.response_start > 0.0);
+                assert!(timing.dom_content_loaded >= timing.response_start);
+                assert!(timing.load_event_end > 0.0);
+                assert!(timing.load_event_end >= timing.dom_content_loaded);
+                Ok(())
+            })
+    }
+
+    fn wait_until_enabled_inner(c: Client) -> impl Future<Item = (), Error = error::CmdError> {
+        let page = "data:text/html,<button id=b disabled>go</button>\
+                    <script>setTimeout(function() { b.disabled = false; }, 500)</script>";
+        c.goto(page)
+            .and_then(|mut c| c.find(Locator::Id("b")))
+            .and_then(|mut e| e.is_enabled().map(move |enabled| (e, enabled)))
+            .and_then(|(mut e, enabled)| {
+                assert!(!enabled);
+                e.wait_until_enabled(Duration::from_secs(10)).map(move |_| e)
+            })
+            .and_then(|mut e| e.is_enabled())
+            .and_then(|enabled| {
+                assert!(enabled);
+                Ok(())
+            })
+    }
+
+    fn debugger_address_inner(c: Client) -> impl Future<Item = (), Error = error::CmdError> {
+        let addr = c.debugger_address().expect("chromedriver reports a debugger address");
+        assert!(!addr.is_empty());
+        // the address should point at the DevTools HTTP endpoint
+        assert!(addr.contains(':'), "{} is not a host:port pair", addr);
+        future::ok(())
+    }
+
+    fn execute_async_inner(c: Client) -> impl Future<Item = (), Error = error::CmdError> {
+        let script = "\
+                      var n = arguments[0], done = arguments[1];\
+                      setTimeout(function() { done(n + 1); }, 100);";
+        c.goto("https://www.wikipedia.org/")
+            .and_then(move |mut c| {
+                c.execute_async(script, vec![Json::from(42)]).map(move |v| (c, v))
+            })
+            .and_then(|(mut c, v)| {
+                assert_eq!(v.as_u64(), Some(43));
+                // this never calls the callback
+                c.execute_async_timeout("", vec![], Duration::from_secs(1))
+                    .then(|r| match r {
+                        Err(error::CmdError::Timeout) => Ok(()),
+                        r => panic!("expected a timeout, got {:?}", r),
+                    })
+            })
+    }
+
+    fn element_exists_inner(c: Client) -> impl Future<Item = (), Error = error::CmdError> {
+        c.goto("https://www.wikipedia.org/")
+            .and_then(|mut c| {
+                c.element_exists(Locator::Css("#searchInput")).map(move |exists| (c, exists))
+            })
+            .and_then(|(mut c, exists)| {
+                assert!(exists);
+                c.element_exists(Locator::Css("#no-such-element"))
+            })
+            .and_then(|exists| {
+                assert!(!exists);
+                Ok(())
+            })
+    }
+
+    fn set_attribute_inner(c: Client) -> impl Future<Item = (), Error = error::CmdError> {
+        c.goto("https://www.wikipedia.org/")
+            .and_then(|mut c| c.find(Locator::Css("#searchInput")))
+            .and_then(|mut e| e.set_attribute("data-test", "fixture").map(move |_| e))
+            .and_then(|mut e| e.attr("data-test"))
+            .and_then(|value| {
+                assert_eq!(value.as_ref().map(String::as_str), Some("fixture"));
+                Ok(())
+            })
+    }
+
+    fn enter_frame_by_css_inner(c: Client) -> impl Future<Item = (), Error = error::CmdError> {
+        let page = "data:text/html,<iframe class=embedded srcdoc='<p id=inner>hello</p>'></iframe>";
+        c.goto(page)
+            .and_then(|c| c.enter_frame_by_css("iframe.embedded"))
+            .and_then(|mut c| c.find(Locator::Id("inner")))
+            .and_then(|mut e| e.text())
+            .and_then(|text| {
+                assert_eq!(text, "hello");
+                Ok(())
+            })
+    }
+
+    fn in_frame_inner(c: Client) -> impl Future<Item = (), Error = error::CmdError> {
+        let page = "data:text/html,<p id=outer>top</p>\
+                    <iframe srcdoc='<p id=inner>hello</p>'></iframe>";
+        c.goto(page)
+            .and_then(|mut c| c.find(Locator::Css("iframe")).map(move |f| (c, f)))
+            .and_then(|(mut c, frame)| {
+                c.in_frame(frame, |c| c.find(Locator::Id("no-such-element")))
+                    .then(move |r| match r {
+                        Err(error::CmdError::NoSuchElement(..)) => Ok(c),
+                        Err(e) => Err(e),
+                        Ok(_) => panic!("found an element that does not exist"),
+                    })
+            })
+            // the focus should be back on the top-level page
+            .and_then(|mut c| c.find(Locator::Id("outer")))
+            .and_then(|mut e| e.text())
+            .and_then(|text| {
+                assert_eq!(text, "top");
+                Ok(())
+            })
+    }
+
+    fn save_source_inner(c: Client) -> impl Future<Item = (), Error = error::CmdError> {
+        c.goto("data:text/html,<p id=saved>archived</p>")
+            .and_then(|mut c| saved_file("source.html", move |path| c.save_source(path)))
+            .map(|src| {
+                let src = String::from_utf8_lossy(&src);
+                assert!(src.contains("<p id=\"saved\">archived</p>"));
+            })
+    }
+
+    fn set_implicit_wait_inner(c: Client) -> impl Future<Item = (), Error = error::CmdError> {
+        let page = "data:text/html,<script>setTimeout(function() {\
+                      var p = document.createElement('p');\
+                      p.id = 'late';\
+                      document.body.appendChild(p);\
+                    }, 1000)</script>";
+        c.goto(page)
+            .and_then(|mut c| c.set_implicit_wait(Duration::from_secs(5)).map(move |_| c))
+            // the element is not there yet, but the server keeps looking for it
+            .and_then(|mut c| c.find(Locator::Id("late")))
+            .map(|_| ())
+    }
+
+    fn location_in_view_inner(c: Client) -> impl Future<Item = (), Error = error::CmdError> {
+        let page = "data:text/html,<div style='height: 5000px'></div><button id=b>below</button>";
+        c.goto(page)
+            .and_then(|mut c| c.find(Locator::Id("b")).map(move |e| (c, e)))
+            .and_then(|(c, mut e)| e.location_in_view().map(move |l| (c, l)))
+            .and_then(|(mut c, location)| c.viewport_size().map(move |size| (location, size)))
+            .and_then(|((x, y), (width, height))| {
+                assert!(x >= 0.0 && x < width as f64, "{} is outside the viewport", x);
+                assert!(y >= 0.0 && y < height as f64, "{} is outside the viewport", y);
+                Ok(())
+            })
+    }
+
+    fn texts_inner(c: Client) -> impl Future<Item = (), Error = error::CmdError> {
+        let page = "data:text/html,<ul id=l><li>one</li><li>two <b>2</b></li><li>three</li></ul>\
+                    <li>outside</li>";
+        c.goto(page)
+            .and_then(|mut c| c.find(Locator::Id("l")))
+            .and_then(|mut list| list.texts(Locator::Css("li")).map(move |texts| (list, texts)))
+            .and_then(|(mut list, texts)| {
+                assert_eq!(texts, vec!["one", "two 2", "three"]);
+                list.texts(Locator::XPath(".//li[position() > 1]"))
+            })
+            .and_then(|texts| {
+                assert_eq!(texts, vec!["two 2", "three"]);
+                Ok(())
+            })
+    }
+
+    fn poll_inner(c: Client) -> impl Future<Item = (), Error = error::CmdError> {
+        let page = "data:text/html,<script>\
+                    setTimeout(function() { document.title = 'ready'; }, 2000);\
+                    </script>";
+        let start = Instant::now();
+        // runs alongside the poll, and should not be held up by it
+        let ticker = tokio::timer::Interval::new_interval(Duration::from_millis(100))
+            .take(10)
+            .for_each(|_| Ok(()))
+            .map_err(|e| error::CmdError::Io(io::Error::new(io::ErrorKind::Other, e)))
+            .map(|_| Instant::now());
+        let poll = c
+            .goto(page)
+            .and_then(|mut c| {
+                c.poll(Duration::from_millis(200), |c| {
+                    c.title().map(|title| title == "ready")
+                })
+                .map(move |_| c)
+            })
+            .map(|c| (c, Instant::now()));
+        poll.join(ticker)
+            .and_then(move |((mut c, polled), ticked)| {
+                assert!(polled.duration_since(start) >= Duration::from_secs(2));
+                assert!(ticked < polled);
+                c.close()
+            })
+    }
+
+    fn expect_tag_inner(c: Client) -> impl Future<Item = (), Error = error::CmdError> {
+        c.goto("data:text/html,<div id='d'>hello</div>")
+            .and_then(|mut c| c.find(Locator::Id("d")))
+            .and_then(|e| e.expect_tag("div"))
+            .and_then(|e| e.expect_tag("span").then(Ok))
+            .and_then(|res| {
+                match res {
+                    Err(error::CmdError::Standard(WebDriverError {
+                        error: webdriver::error::ErrorStatus::UnknownError,
+                        ref message,
+                        ..
+                    })) => {
+                        assert!(message.contains("<span>"));
+                        assert!(message.contains("<div>"));
+                    }
+                    Err(e) => return Err(e),
+                    Ok(_) => panic!("expect_tag accepted the wrong tag name"),
+                }
+                Ok(())
+            })
+    }
+
+    fn minimize_window_inner(c: Client) -> impl Future<Item = (), Error = error::CmdError> {
+        c.goto("data:text/html,minimize me")
+            .and_then(|mut c| c.minimize_window().map(move |rect| (c, rect)))
+            .and_then(|(mut c, rect)| {
+                c.execute("return document.visibilityState", vec![])
+                    .map(move |state| (c, rect, state))
+            })
+            .and_then(|(mut c, rect, state)| {
+                assert_eq!(state.as_str(), Some("hidden"));
+                c.get_window_rect().map(move |now| assert_eq!(rect, now))
+            })
+    }
+
+    fn execute_typed_inner(c: Client) -> impl Future<Item = (), Error = error::CmdError> {
+        #[derive(Serialize)]
+        struct Point {
+            x: u32,
+            y: u32,
+        }
+
+        #[derive(Debug, Deserialize, PartialEq)]
+        struct Totals {
+            sum: u32,
+            prod: u32,
+        }
+
+        c.goto("data:text/html,typed")
+            .and_then(|mut c| {
+                c.execute_typed(
+                    "var p = arguments[0]; return { sum: p.x + p.y, prod: p.x * p.y };",
+                    Point { x: 3, y: 4 },
+                )
+                .map(move |totals: Totals| {
+                    assert_eq!(totals, Totals { sum: 7, prod: 12 });
+                    c
+                })
+            })
+            .and_then(|mut c| {
+                // arrays are passed as a single argument too
+                c.execute_typed(
+                    "return [arguments.length, arguments[0].length];",
+                    vec![1, 2, 3],
+                )
+                .map(|lens: (u32, u32)| assert_eq!(lens, (1, 3)))
+            })
+    }
+
+    fn clear_cache_inner(c: Client) -> impl Future<Item = (), Error = error::CmdError> {
+        c.goto("https://www.wikipedia.org/")
+            .and_then(|mut c| c.clear_cache().map(move |_| c))
+            .and_then(|c| c.goto("https://www.wikipedia.org/"))
+            .and_then(|mut c| c.current_url())
+            .map(|url| assert_eq!(url.as_ref(), "https://www.wikipedia.org/"))
+    }
+
+    fn type_text_inner(c: Client) -> impl Future<Item = (), Error = error::CmdError> {
+        // an input that ignores key presses, like some framework-controlled inputs do
+        let page = "data:text/html,<input id=i value=old \
+                    onkeydown=\"event.preventDefault()\" \
+                    oninput=\"this.setAttribute('data-seen', 'yes')\">";
+        c.goto(page)
+            .and_then(|mut c| c.find(Locator::Id("i")))
+            .and_then(|mut e| e.type_text("hello").map(move |_| e))
+            .and_then(|mut e| e.prop("value").map(move |value| (e, value)))
+            .and_then(|(mut e, value)| {
+                assert_eq!(value.as_ref().map(String::as_str), Some("hello"));
+                e.attr("data-seen")
+            })
+            .map(|seen| assert_eq!(seen.as_ref().map(String::as_str), Some("yes")))
+    }
+
+    fn quit_browser_inner(mut c: Client) -> impl Future<Item = (), Error = error::CmdError> {
+        let addr = c
+            .debugger_address()
+            .expect("chromedriver reports a debugger address");
+        c.quit_browser()
+            .and_then(|_| sleep(Duration::from_secs(1)))
+            .and_then(move |_| {
+                // the browser's DevTools endpoint should be gone along with the browser
+                let uri = format!("http://{}/json/version", addr).parse().unwrap();
+                hyper::Client::new().get(uri).then(|res| {
+                    assert!(res.is_err(), "browser is still running");
+                    Ok(())
+                })
+            })
+    }
+
+    fn wait_for_navigation_timeout_inner(
+        c: Client,
+    ) -> impl Future<Item = (), Error = error::CmdError> {
+        // a page that never navigates anywhere
+        c.goto("data:text/html,stay here")
+            .and_then(|c| c.wait_for_navigation_timeout(None, Duration::from_secs(1)))
+            .then(|res| match res {
+                Err(error::CmdError::WaitTimeout) => Ok(()),
+                Err(e) => Err(e),
+                Ok(_) => panic!("navigation was detected although none happened"),
+            })
+    }
+
+    fn refill_inner(c: Client) -> impl Future<Item = (), Error = error::CmdError> {
+        c.goto("data:text/html,<input id=i value=initial>")
+            .and_then(|mut c| c.find(Locator::Id("i")))
+            .and_then(|mut e| e.refill("first").map(move |_| e))
+            .and_then(|mut e| e.prop("value").map(move |value| (e, value)))
+            .and_then(|(mut e, value)| {
+                assert_eq!(value.as_ref().map(String::as_str), Some("first"));
+                e.refill("second").map(move |_| e)
+            })
+            .and_then(|mut e| e.prop("value"))
+            .map(|value| assert_eq!(value.as_ref().map(String::as_str), Some("second")))
+    }
+
+    fn computed_styles_inner(c: Client) -> impl Future<Item = (), Error = error::CmdError> {
+        let page = "data:text/html,<p id=p style='display: block; color: rgb(255, 0, 0); \
+                    font-size: 20px'>styled</p>";
+        c.goto(page)
+            .and_then(|mut c| c.find(Locator::Id("p")))
+            .and_then(|mut e| e.computed_styles(&["display", "color", "font-size"]))
+            .map(|styles| {
+                assert_eq!(styles.len(), 3);
+                assert_eq!(styles, vec!["block", "rgb(255, 0, 0)", "20px"]);
+            })
+    }
+
+    fn has_attr_inner(c: Client) -> impl Future<Item = (), Error = error::CmdError> {
+        c.goto("data:text/html,<input id=off disabled><input id=on>")
+            .and_then(|mut c| c.find(Locator::Id("off")).map(move |e| (c, e)))
+            .and_then(|(c, mut e)| e.has_attr("disabled").map(move |has| (c, has)))
+            .and_then(|(mut c, has)| {
+                assert!(has, "disabled input lacks the disabled attribute");
+                c.find(Locator::Id("on"))
+            })
+            .and_then(|mut e| e.has_attr("disabled"))
+            .map(|has| assert!(!has, "enabled input has the disabled attribute"))
+    }
+
+    fn update_window_rect_inner(c: Client) -> impl Future<Item = (), Error = error::CmdError> {
+        c.goto("data:text/html,resize me")
+            .and_then(|mut c| c.set_window_rect(10, 20, 800, 600).map(move |_| c))
+            .and_then(|mut c| {
+                let rect = PartialWindowRect {
+                    width: Some(700),
+                    ..PartialWindowRect::default()
+                };
+                c.update_window_rect(rect).map(move |_| c)
+            })
+            .and_then(|mut c| c.get_window_rect())
+            .map(|(x, y, width, height)| assert_eq!((x, y, width, height), (10, 20, 700, 600)))
+    }
+
+    fn save_pdf_inner(c: Client) -> impl Future<Item = (), Error = error::CmdError> {
+        c.goto("data:text/html,<h1>report</h1>")
+            .and_then(|mut c| {
+                let opts = PrintOptions::default();
+                saved_file("print.pdf", move |path| c.save_pdf(path, opts))
+            })
+            .map(|pdf| assert!(pdf.starts_with(b"%PDF")))
+    }
+
+    fn equals_inner(c: Client) -> impl Future<Item = (), Error = error::CmdError> {
+        c.goto("data:text/html,<p id=a class=x>a</p><p id=b>b</p>")
+            .and_then(|mut c| c.find(Locator::Id("a")).map(move |a| (c, a)))
+            .and_then(|(mut c, a)| c.find(Locator::Css(".x")).map(move |same| (c, a, same)))
+            .and_then(|(mut c, a, same)| c.find(Locator::Id("b")).map(move |b| (a, same, b)))
+            .and_then(|(mut a, same, b)| {
+                a.equals(&same).and_then(move |equal| {
+                    assert!(equal, "the same node compared unequal");
+                    a.equals(&b)
+                })
+            })
+            .map(|equal| assert!(!equal, "different nodes compared equal"))
+    }
+
+    /// Spawn an HTTP server that serves a page with a single image on it, at `/logo.png`.
+    ///
+    /// Returns the URL of the page.
+    fn spawn_image_page() -> String {
+        use hyper::{Body, Response};
+
+        // a 1x1 transparent PNG
+        const LOGO: &[u8] = b"\x89PNG\r\n\x1a\n\
+            \x00\x00\x00\x0dIHDR\x00\x00\x00\x01\x00\x00\x00\x01\x08\x06\x00\x00\x00\x1f\x15\xc4\x89\
+            \x00\x00\x00\x0aIDAT\x78\x9c\x63\x00\x01\x00\x00\x05\x00\x01\x0d\x0a\x2d\xb4\
+            \x00\x00\x00\x00IEND\xae\x42\x60\x82";
+
+        spawn_http(|req| {
+            let (content_type, body) = if req.uri().path() == "/logo.png" {
+                ("image/png", Body::from(LOGO))
+            } else {
+                ("text/html", Body::from("<img id=logo src=/logo.png>"))
+            };
+            let rsp = Response::builder()
+                .header(hyper::header::CONTENT_TYPE, content_type)
+                .body(body)
+                .unwrap();
+            Ok::<_, hyper::Error>(rsp)
+        })
+    }
+
+    fn set_blocked_urls_inner(c: Client) -> impl Future<Item = (), Error = error::CmdError> {
+        let url = spawn_image_page();
+        let check = "var img = arguments[0]; return img.complete && img.naturalWidth === 0;";
+        c.goto("about:blank")
+            .and_then(|mut c| c.set_blocked_urls(&["*logo.png*"]).map(move |_| c))
+            .and_then(move |c| c.goto(&url))
+            .and_then(|mut c| c.find(Locator::Id("logo")).map(move |img| (c, img)))
+            .and_then(move |(mut c, img)| c.execute(check, vec![via_json!(&img.web_element())]))
+            .map(|failed| assert_eq!(failed, Json::Bool(true), "blocked image was loaded"))
+    }
+
+    /// Spawn an HTTP server that responds to every request with a page that shows the value of
+    /// the `x-staging-auth` request header.
+    ///
+    /// Returns the URL of the server.
+    fn spawn_header_echo() -> String {
+        use hyper::{Body, Response};
+
+        spawn_http(|req| {
+            let value = req
+                .headers()
+                .get("x-staging-auth")
+                .and_then(|v| v.to_str().ok())
+                .unwrap_or("missing")
+                .to_string();
+            let rsp = Response::builder()
+                .header(hyper::header::CONTENT_TYPE, "text/html")
+                .body(Body::from(format!("<p id=echo>{}</p>", value)))
+                .unwrap();
+            Ok::<_, hyper::Error>(rsp)
+        })
+    }
+
+    fn set_request_headers_inner(mut c: Client) -> impl Future<Item = (), Error = error::CmdError> {
+        let url = spawn_header_echo();
+        let mut headers = std::collections::HashMap::new();
+        headers.insert("X-Staging-Auth".to_string(), "letmein".to_string());
+        c.set_request_headers(headers)
+            .and_then(move |_| c.goto(&url))
+            .and_then(|mut c| c.find(Locator::Id("echo")))
+            .and_then(|mut e| e.text())
+            .map(|text| assert_eq!(text, "letmein"))
+    }
+
+    fn clear_editable_inner(c: Client) -> impl Future<Item = (), Error = error::CmdError> {
+        c.goto("data:text/html,<div id=d contenteditable>some <b>rich</b> text</div>")
+            .and_then(|mut c| c.find(Locator::Id("d")))
+            .and_then(|mut e| e.clear_editable().map(move |_| e))
+            .and_then(|mut e| e.text())
+            .map(|text| assert_eq!(text, ""))
+    }
+
+    fn wait_for_alert_inner(c: Client) -> impl Future<Item = (), Error = error::CmdError> {
+        let page = "data:text/html,<script>\
+                    setTimeout(function() { alert('finished'); }, 1000);\
+                    </script>";
+        c.goto(page)
+            .and_then(|mut c| c.wait_for_alert(Duration::from_secs(10)))
+            .map(|text| assert_eq!(text, "finished"))
+    }
+
+    fn window_with_title_inner(c: Client) -> impl Future<Item = (), Error = error::CmdError> {
+        c.goto("data:text/html,<title>Quarterly report</title>")
+            .and_then(|mut c| c.new_tab().map(move |_| c))
+            .and_then(|c| c.goto("data:text/html,<title>Settings</title>"))
+            .and_then(|mut c| {
+                c.window_with_title(|title| title.contains("report"))
+                    .map(move |found| (c, found))
+            })
+            .and_then(|(mut c, found)| {
+                assert!(found.is_some(), "no window matched the title");
+                c.title()
+            })
+            .map(|title| assert_eq!(title, "Quarterly report"))
+    }
+
+    fn in_viewport_inner(c: Client) -> impl Future<Item = (), Error = error::CmdError> {
+        c.goto("data:text/html,<div style='height: 5000px'></div><p id=below>down here</p>")
+            .and_then(|mut c| c.find(Locator::Id("below")))
+            .and_then(|mut e| e.in_viewport().map(move |visible| (e, visible)))
+            .and_then(|(mut e, visible)| {
+                assert!(!visible, "element below the fold is in the viewport");
+                e.location_in_view().map(move |_| e)
+            })
+            .and_then(|mut e| e.in_viewport())
+            .map(|visible| assert!(visible, "element scrolled to is not in the viewport"))
+    }
+
+    fn find_all_limited_inner(c: Client) -> impl Future<Item = (), Error = error::CmdError> {
+        c.goto("https://en.wikipedia.org/wiki/Foobar")
+            .and_then(|mut c| c.find_all_limited(Locator::Css("a"), 5))
+            .and_then(|mut links| {
+                assert_eq!(links.len(), 5);
+                // the references must be usable like any other
+                links[4].tag_name()
+            })
+            .map(|tag| assert_eq!(tag, "a"))
+    }
+
+    fn focused_window_valid_inner(c: Client) -> impl Future<Item = (), Error = error::CmdError> {
+        c.goto("data:text/html,first")
+            .and_then(|mut c| c.focused_window_valid().map(move |valid| (c, valid)))
+            .and_then(|(mut c, valid)| {
+                assert!(valid, "the initial window is not valid");
+                c.new_tab().map(move |_| c)
+            })
+            // close the new tab, but leave the focus where it was
+            .and_then(|mut c| c.close_window().map(move |_| c))
+            .and_then(|mut c| c.focused_window_valid())
+            .map(|valid| assert!(!valid, "the closed window is still valid"))
+    }
+
+    fn focus_inner(c: Client) -> impl Future<Item = (), Error = error::CmdError> {
+        let is_active = "return document.activeElement === arguments[0];";
+        c.goto("data:text/html,<input id=i>")
+            .and_then(|mut c| c.find(Locator::Id("i")).map(move |e| (c, e)))
+            .and_then(|(c, mut e)| e.focus().map(move |_| (c, e)))
+            .and_then(move |(mut c, e)| {
+                c.execute(is_active, vec![via_json!(&e.web_element())])
+                    .map(move |active| (c, e, active))
+            })
+            .and_then(|(c, mut e, active)| {
+                assert_eq!(active, Json::Bool(true));
+                e.blur().map(move |_| (c, e))
+            })
+            .and_then(move |(mut c, e)| c.execute(is_active, vec![via_json!(&e.web_element())]))
+            .and_then(|active| {
+                assert_eq!(active, Json::Bool(false));
+                Ok(())
+            })
+    }
+
+    fn restart_session_inner(c: Client) -> impl Future<Item = (), Error = error::CmdError> {
+        c.goto("https://www.wikipedia.org/")
+            .and_then(|mut c| c.session_id().map(move |id| (c, id)))
+            .and_then(|(c, old)| {
+                let other = c.clone();
+                c.restart_session().map(move |c| (c, other, old))
+            })
+            .and_then(|(mut c, other, old)| c.session_id().map(move |new| (c, other, old, new)))
+            .and_then(|(c, other, old, new)| {
+                assert!(new.is_some());
+                assert_ne!(old, new);
+                // clones see the capabilities of the new session too
+                match (c.capabilities(), other.capabilities()) {
+                    (Some(caps), Some(other)) => assert!(Arc::ptr_eq(&caps, &other)),
+                    _ => panic!("new session did not report capabilities"),
+                }
+                c.goto("https://en.wikipedia.org/wiki/Foobar")
+            })
+            .and_then(|mut c| c.current_url())
+            .and_then(|url| {
+                assert_eq!(url.as_ref(), "https://en.wikipedia.org/wiki/Foobar");
+                Ok(())
+            })
+    }
+
+    fn not_interactable_inner(c: Client) -> impl Future<Item = (), Error = error::CmdError> {
+        c.goto(r#"data:text/html,<button id="hidden" style="display:none">hidden</button>"#)
+            .and_then(|mut c| c.find(Locator::Id("hidden")))
+            .and_then(|e| e.click())
+            .then(|r| match r {
+                Err(error::CmdError::NotInteractable(..)) => Ok(()),
+                Err(e) => panic!("expected NotInteractable, got {}", e),
+                Ok(_) => panic!("clicking a hidden button should fail"),
+            })
+    }
+
+    fn wait_for_title_inner(c: Client) -> impl Future<Item = (), Error = error::CmdError> {
+        c.goto(
+            "data:text/html,<title>Before</title>\
+             <a id='go' href='javascript:void(0)' \
+             onclick='setTimeout(function() { document.title = \"After the change\"; }, 500)'>\
+             go</a>",
+        )
+        .and_then(|mut c| c.find(Locator::Id("go")))
+        .and_then(|e| e.click())
+        .and_then(|c| c.wait_for_title(|title| title.contains("After"), Duration::from_secs(5)))
+        .and_then(|mut c| c.title())
+        .and_then(|title| {
+            assert_eq!(title, "After the change");
+            Ok(())
+        })
+    }
+
+    fn send_files_inner(c: Client) -> impl Future<Item = (), Error = error::CmdError> {
+        let dir = std::env::temp_dir();
+        let files = vec![
+            dir.join(format!("fantoccini-upload-{}-a.txt", std::process::id())),
+            dir.join(format!("fantoccini-upload-{}-b.txt", std::process::id())),
+        ];
+        for file in &files {
+            std::fs::write(file, "upload me").unwrap();
+        }
+
+        c.goto(r#"data:text/html,<input type="file" id="files" multiple>"#)
+            .and_then(|mut c| c.find(Locator::Id("files")))
+            .and_then(move |mut e| {
+                let paths = files.iter().map(|f| f.as_path()).collect::<Vec<_>>();
+                e.send_files(&paths)
+                    .map(move |_| e)
+                    .then(move |r| {
+                        for file in &files {
+                            let _ = std::fs::remove_file(file);
+                        }
+                        r
+                    })
+            })
+            .and_then(|e| {
+                let mut c = e.client();
+                c.execute("return document.getElementById('files').files.length", vec![])
+            })
+            .and_then(|count| {
+                assert_eq!(count, Json::from(2));
+                Ok(())
+            })
+    }
+
+    fn accessibility_tree_inner(c: Client) -> impl Future<Item = (), Error = error::CmdError> {
+        c.goto("data:text/html,<h1>Hello</h1><button>Press me</button>")
+            .and_then(|mut c| c.accessibility_tree())
+            .and_then(|tree| {
+                let nodes = tree.as_array().expect("tree should be an array of nodes");
+                assert!(nodes
+                    .iter()
+                    .any(|node| node["role"]["value"] == "RootWebArea"));
+                Ok(())
+            })
+    }
+
+    fn session_not_created_inner(webdriver: &str, caps: webdriver::capabilities::Capabilities) {
+        let mut rt = tokio::runtime::current_thread::Runtime::new().unwrap();
+        match rt.block_on(Client::with_capabilities(webdriver, caps)) {
+            Err(error::NewSessionError::SessionNotCreated { message, .. }) => {
+                assert!(!message.is_empty());
+            }
+            Err(e) => panic!("unexpected error: {:?}", e),
+            Ok(_) => panic!("session was created with a nonexistent browser binary"),
+        }
+    }
+
+    mod chrome {
+        use super::*;
+
+        #[test]
+        fn it_works() {
+            tester!(works_inner, "chrome")
+        }
+        #[test]
+        fn it_clicks() {
+            tester!(clicks_inner, "chrome")
+        }
+        #[test]
+        fn it_clicks_by_locator() {
+            tester!(clicks_inner_by_locator, "chrome")
+        }
+        #[test]
+        fn it_sends_keys_and_clear_input() {
+            tester!(send_keys_and_clear_input_inner, "chrome")
+        }
+        #[test]
+        fn it_can_be_raw() {
+            tester!(raw_inner, "chrome")
+        }
+        #[test]
+        #[ignore]
+        fn it_can_get_and_set_window_size() {
+            tester!(window_size_inner, "chrome")
+        }
+        #[test]
+        #[ignore]
+        fn it_can_get_and_set_window_position() {
+            tester!(window_position_inner, "chrome")
+        }
+        #[test]
+        #[ignore]
+        fn it_can_get_and_set_window_rect() {
+            tester!(window_rect_inner, "chrome")
+        }
+        #[test]
+        fn it_finds_all() {
+            tester!(finds_all_inner, "chrome")
+        }
+        #[test]
+        #[ignore]
+        fn it_persists() {
+            tester!(persist_inner, "chrome")
+        }
+        #[test]
+        fn it_navigates_with_post() {
+            tester!(navigate_post_inner, "chrome")
+        }
+        #[test]
+        fn it_reports_session_not_created() {
+            let mut caps = serde_json::map::Map::new();
+            let opts = serde_json::json!({ "binary": "/nonexistent/chromium" });
+            caps.insert("goog:chromeOptions".to_string(), opts);
+            session_not_created_inner("http://localhost:9515", caps)
+        }
+        #[test]
+        fn it_screenshots_entire_element() {
+            tester!(element_screenshot_full_inner, "chrome")
+        }
+        #[test]
+        fn it_finds_by_escaped_id() {
+            tester!(find_by_escaped_id_inner, "chrome")
+        }
+        #[test]
+        fn it_downloads_to_writer() {
+            tester!(download_to_writer_inner, "chrome")
+        }
+        #[test]
+        fn it_switches_to_default_content() {
+            tester!(frames_inner, "chrome")
+        }
+        #[test]
+        fn it_detects_stale_elements() {
+            tester!(stale_inner, "chrome")
+        }
+        #[test]
+        fn it_sets_permissions() {
+            tester!(set_permission_inner, "chrome")
+        }
+        #[test]
+        fn it_fetches() {
+            tester!(fetch_inner, "chrome")
+        }
+        #[test]
+        fn it_waits_for_clickable() {
+            tester!(wait_for_clickable_inner, "chrome")
+        }
+        #[test]
+        fn it_closes_window_and_switches() {
+            tester!(close_window_and_switch_inner, "chrome")
+        }
+        #[test]
+        fn it_hovers() {
+            tester!(hover_inner, "chrome")
+        }
+        #[test]
+        fn it_executes_files() {
+            tester!(execute_file_inner, "chrome")
+        }
+        #[test]
+        fn it_finds_by_tag_name() {
+            tester!(finds_by_tag_name_inner, "chrome")
+        }
+        #[test]
+        fn it_finds_by_class_name() {
+            tester!(finds_by_class_name_inner, "chrome")
+        }
+        #[test]
+        fn it_enforces_deadlines() {
+            tester!(deadline_inner, "chrome")
+        }
+        #[test]
+        fn it_accepts_insecure_certs() {
+            tester!(insecure_certs_inner, "chrome", |b| {
+                b.accept_insecure_certs(true);
+            })
+        }
+        #[test]
+        fn it_reads_aria_role_and_label() {
+            tester!(aria_inner, "chrome")
+        }
+        #[test]
+        fn it_retries_stale_elements() {
+            tester!(retry_stale_inner, "chrome")
+        }
+        #[test]
+        fn it_attaches_to_existing_sessions() {
+            fn attach(c: Client) -> impl Future<Item = (), Error = error::CmdError> {
+                attach_inner(c, "http://localhost:9515")
+            }
+            tester!(attach, "chrome")
+        }
+        #[test]
+        fn it_clears_via_keyboard() {
+            tester!(clear_via_keyboard_inner, "chrome")
+        }
+        #[test]
+        fn it_filters_cookies_by_domain() {
+            tester!(cookies_for_domain_inner, "chrome")
+        }
+        #[test]
+        fn it_observes_commands() {
+            tester!(command_observer_inner, "chrome")
+        }
+        #[test]
+        fn it_executes_element_queries() {
+            tester!(execute_elements_inner, "chrome")
+        }
+        #[test]
+        fn it_uses_local_storage() {
+            tester!(local_storage_inner, "chrome")
+        }
+        #[test]
+        fn it_uses_session_storage() {
+            tester!(session_storage_inner, "chrome")
+        }
+        #[test]
+        fn it_focuses_and_blurs() {
+            tester!(focus_inner, "chrome")
+        }
+        #[test]
+        fn it_screenshots_full_page() {
+            tester!(screenshot_full_page_inner, "chrome")
+        }
+        #[test]
+        fn it_restarts_session() {
+            tester!(restart_session_inner, "chrome")
+        }
+        #[test]
+        fn it_reports_not_interactable() {
+            tester!(not_interactable_inner, "chrome")
+        }
+        #[test]
+        fn it_waits_for_title() {
+            tester!(wait_for_title_inner, "chrome")
+        }
+        #[test]
+        fn it_sends_files() {
+            tester!(send_files_inner, "chrome")
+        }
+        #[test]
+        fn it_reads_accessibility_tree() {
+            tester!(accessibility_tree_inner, "chrome")
+        }
+        #[test]
+        fn it_pauses_action_chains() {
+            tester!(action_pause_inner, "chrome")
+        }
+        #[test]
+        fn it_selects_all() {
+            tester!(select_all_inner, "chrome")
+        }
+        #[test]
+        fn it_waits_for_stable_url() {
+            tester!(goto_wait_stable_inner, "chrome")
+        }
+        #[test]
+        fn it_opens_new_tabs() {
+            tester!(new_tab_inner, "chrome")
+        }
+        #[test]
+        fn it_clicks_at_offsets() {
+            tester!(click_at_inner, "chrome")
+        }
+        #[test]
+        fn it_submits_and_waits() {
+            tester!(submit_and_wait_inner, "chrome")
+        }
+        #[test]
+        fn it_downloads_to_directory() {
+            tester!(download_directory_inner, "chrome")
+        }
+        #[test]
+        fn it_waits_for_downloads() {
+            tester!(wait_for_download_inner, "chrome")
+        }
+        #[test]
+        fn it_finds_element_from_point() {
+            tester!(element_from_point_inner, "chrome")
+        }
+        #[test]
+        fn it_scrolls() {
+            tester!(scroll_inner, "chrome")
+        }
+        #[test]
+        #[ignore]
+        fn it_reads_viewport_size() {
+            tester!(viewport_size_inner, "chrome")
+        }
+        #[test]
+        fn it_moves_with_offset() {
+            tester!(move_with_offset_inner, "chrome")
+        }
+        #[test]
+        fn it_clears_browser_state() {
+            tester!(clear_browser_state_inner, "chrome")
+        }
+        #[test]
+        fn it_waits_for_url_change() {
+            tester!(wait_for_url_change_inner, "chrome")
+        }
+        #[test]
+        fn it_reports_error_details() {
+            tester!(error_details_inner, "chrome")
+        }
+        #[test]
+        fn it_sends_keys_in_chunks() {
+            tester!(send_keys_chunked_inner, "chrome")
+        }
+        #[test]
+        fn it_reads_props_for_all() {
+            tester!(props_for_inner, "chrome")
+        }
+        #[test]
+        fn it_screenshots_after_goto() {
+            tester!(goto_screenshot_inner, "chrome")
+        }
+        #[test]
+        fn it_finds_with_compiled_locators() {
+            tester!(compiled_locator_inner, "chrome")
+        }
+        #[test]
+        fn it_reads_navigation_timing() {
+            tester!(navigation_timing_inner, "chrome")
+        }
+        #[test]
+        fn it_waits_until_enabled() {
+            tester!(wait_until_enabled_inner, "chrome")
+        }
+        #[test]
+        fn it_reads_debugger_address() {
+            tester!(debugger_address_inner, "chrome")
+        }
+        #[test]
+        fn it_screenshots_with_options() {
+            tester!(screenshot_opts_inner, "chrome")
+        }
+        #[test]
+        fn it_executes_async_scripts() {
+            tester!(execute_async_inner, "chrome")
+        }
+        #[test]
+        fn it_checks_element_existence() {
+            tester!(element_exists_inner, "chrome")
+        }
+        #[test]
+        fn it_sets_attributes() {
+            tester!(set_attribute_inner, "chrome")
+        }
+        #[test]
+        fn it_enters_frames_by_css() {
+            tester!(enter_frame_by_css_inner, "chrome")
+        }
+        #[test]
+        fn it_runs_in_frames() {
+            tester!(in_frame_inner, "chrome")
+        }
+        #[test]
+        fn it_saves_source() {
+            tester!(save_source_inner, "chrome")
+        }
+        #[test]
+        fn it_sets_implicit_wait() {
+            tester!(set_implicit_wait_inner, "chrome")
+        }
+        #[test]
+        fn it_reads_location_in_view() {
+            tester!(location_in_view_inner, "chrome")
+        }
+        #[test]
+        fn it_proxies_raw_requests() {
+            tester!(proxy_raw_requests_inner, "chrome", use_stub_proxy)
+        }
+        #[test]
+        fn it_reads_texts() {
+            tester!(texts_inner, "chrome")
+        }
+        #[test]
+        fn it_polls_without_blocking() {
+            tester!(poll_inner, "chrome")
+        }
+        #[test]
+        fn it_expects_tags() {
+            tester!(expect_tag_inner, "chrome")
+        }
+        #[test]
+        #[ignore]
+        fn it_minimizes_window() {
+            tester!(minimize_window_inner, "chrome")
+        }
+        #[test]
+        fn it_executes_typed_scripts() {
+            tester!(execute_typed_inner, "chrome")
+        }
+        #[test]
+        fn it_clears_cache() {
+            tester!(clear_cache_inner, "chrome")
+        }
+        #[test]
+        fn it_types_text() {
+            tester!(type_text_inner, "chrome")
+        }
+        #[test]
+        fn it_quits_browser() {
+            tester!(quit_browser_inner, "chrome")
+        }
+        #[test]
+        fn it_waits_for_navigation_with_timeout() {
+            tester!(wait_for_navigation_timeout_inner, "chrome")
+        }
+        #[test]
+        fn it_refills_inputs() {
+            tester!(refill_inner, "chrome")
+        }
+        #[test]
+        fn it_saves_element_screenshots() {
+            tester!(element_save_screenshot_inner, "chrome")
+        }
+        #[test]
+        fn it_saves_screenshots() {
+            tester!(save_screenshot_inner, "chrome")
+        }
+        #[test]
+        fn it_reads_computed_styles() {
+            tester!(computed_styles_inner, "chrome")
+        }
+        #[test]
+        fn it_checks_attributes() {
+            tester!(has_attr_inner, "chrome")
+        }
+        #[test]
+        #[ignore]
+        fn it_updates_window_rect() {
+            tester!(update_window_rect_inner, "chrome")
+        }
+        #[test]
+        fn it_saves_pdf() {
+            tester!(save_pdf_inner, "chrome")
+        }
+        #[test]
+        fn it_compares_elements() {
+            tester!(equals_inner, "chrome")
+        }
+        #[test]
+        fn it_blocks_urls() {
+            tester!(set_blocked_urls_inner, "chrome")
+        }
+        #[test]
+        fn it_sets_request_headers() {
+            tester!(set_request_headers_inner, "chrome")
+        }
+        #[test]
+        fn it_clears_editable_regions() {
+            tester!(clear_editable_inner, "chrome")
+        }
+        #[test]
+        fn it_waits_for_alerts() {
+            tester!(wait_for_alert_inner, "chrome")
+        }
+        #[test]
+        fn it_finds_window_with_title() {
+            tester!(window_with_title_inner, "chrome")
+        }
+        #[test]
+        fn it_checks_viewport_visibility() {
+            tester!(in_viewport_inner, "chrome")
+        }
+        #[test]
+        fn it_finds_limited_elements() {
+            tester!(find_all_limited_inner, "chrome")
+        }
+        #[test]
+        fn it_checks_focused_window() {
+            tester!(focused_window_valid_inner, "chrome")
+        }
+    }
+
+    mod firefox {
+        use super::*;
+
+        #[test]
+        fn it_works() {
+            tester!(works_inner, "firefox")
+        }
+        #[test]
+        fn it_clicks() {
+            tester!(clicks_inner, "firefox")
+        }
+        #[test]
+        fn it_clicks_by_locator() {
+            tester!(clicks_inner_by_locator, "firefox")
+        }
+        #[test]
+        fn it_sends_keys_and_clear_input() {
+            tester!(send_keys_and_clear_input_inner, "firefox")
+        }
+        #[test]
+        fn it_can_be_raw() {
+            tester!(raw_inner, "firefox")
+        }
+        #[test]
+        #[ignore]
+        fn it_can_get_and_set_window_size() {
+            tester!(window_size_inner, "firefox")
+        }
+        #[test]
+        #[ignore]
+        fn it_can_get_and_set_window_position() {
+            tester!(window_position_inner, "firefox")
+        }
+        #[test]
+        #[ignore]
+        fn it_can_get_and_set_window_rect() {
+            tester!(window_rect_inner, "firefox")
+        }
+        #[test]
+        fn it_finds_all() {
+            tester!(finds_all_inner, "firefox")
+        }
+        #[test]
+        #[ignore]
+        fn it_persists() {
+            tester!(persist_inner, "firefox")
+        }
+        #[test]
+        fn it_navigates_with_post() {
+            tester!(navigate_post_inner, "firefox")
+        }
+        #[test]
+        fn it_reports_session_not_created() {
+            let mut caps = serde_json::map::Map::new();
+            let opts = serde_json::json!({ "binary": "/nonexistent/firefox" });
+            caps.insert("moz:firefoxOptions".to_string(), opts);
+            session_not_created_inner("http://localhost:4444", caps)
+        }
+        #[test]
+        fn it_finds_by_escaped_id() {
+            tester!(find_by_escaped_id_inner, "firefox")
+        }
+        #[test]
+        fn it_downloads_to_writer() {
+            tester!(download_to_writer_inner, "firefox")
+        }
+        #[test]
+        fn it_switches_to_default_content() {
+            tester!(frames_inner, "firefox")
+        }
+        #[test]
+        fn it_detects_stale_elements() {
+            tester!(stale_inner, "firefox")
+        }
+        #[test]
+        fn it_fetches() {
+            tester!(fetch_inner, "firefox")
+        }
+        #[test]
+        fn it_waits_for_clickable() {
+            tester!(wait_for_clickable_inner, "firefox")
+        }
+        #[test]
+        fn it_closes_window_and_switches() {
+            tester!(close_window_and_switch_inner, "firefox")
+        }
+        #[test]
+        fn it_hovers() {
+            tester!(hover_inner, "firefox")
+        }
+        #[test]
+        fn it_executes_files() {
+            tester!(execute_file_inner, "firefox")
+        }
+        #[test]
+        fn it_finds_by_tag_name() {
+            tester!(finds_by_tag_name_inner, "firefox")
+        }
+        #[test]
+        fn it_finds_by_class_name() {
+            tester!(finds_by_class_name_inner, "firefox")
+        }
+        #[test]
+        fn it_enforces_deadlines() {
+            tester!(deadline_inner, "firefox")
+        }
+        #[test]
+        fn it_accepts_insecure_certs() {
+            tester!(insecure_certs_inner, "firefox", |b| {
+                b.accept_insecure_certs(true);
+            })
+        }
+        #[test]
+        fn it_reads_aria_role_and_label() {
+            tester!(aria_inner, "firefox")
+        }
+        #[test]
+        fn it_retries_stale_elements() {
+            tester!(retry_stale_inner, "firefox")
+        }
+        #[test]
+        fn it_attaches_to_existing_sessions() {
+            fn attach(c: Client) -> impl Future<Item = (), Error = error::CmdError> {
+                attach_inner(c, "http://localhost:4444")
+            }
+            tester!(attach, "firefox")
+        }
+        #[test]
+        fn it_clears_via_keyboard() {
+            tester!(clear_via_keyboard_inner, "firefox")
+        }
+        #[test]
+        fn it_filters_cookies_by_domain() {
+            tester!(cookies_for_domain_inner, "firefox")
+        }
+        #[test]
+        fn it_observes_commands() {
+            tester!(command_observer_inner, "firefox")
+        }
+        #[test]
+        fn it_executes_element_queries() {
+            tester!(execute_elements_inner, "firefox")
+        }
+        #[test]
+        fn it_uses_local_storage() {
+            tester!(local_storage_inner, "firefox")
+        }
+        #[test]
+        fn it_uses_session_storage() {
+            tester!(session_storage_inner, "firefox")
+        }
+        #[test]
+        fn it_focuses_and_blurs() {
+            tester!(focus_inner, "firefox")
+        }
+        #[test]
+        fn it_screenshots_full_page() {
+            tester!(screenshot_full_page_inner, "firefox")
+        }
+        #[test]
+        fn it_restarts_session() {
+            tester!(restart_session_inner, "firefox")
+        }
+        #[test]
+        fn it_reports_not_interactable() {
+            tester!(not_interactable_inner, "firefox")
+        }
+        #[test]
+        fn it_waits_for_title() {
+            tester!(wait_for_title_inner, "firefox")
+        }
+        #[test]
+        fn it_sends_files() {
+            tester!(send_files_inner, "firefox")
+        }
+        #[test]
+        fn it_pauses_action_chains() {
+            tester!(action_pause_inner, "firefox")
+        }
+        #[test]
+        fn it_selects_all() {
+            tester!(select_all_inner, "firefox")
+        }
+        #[test]
+        fn it_waits_for_stable_url() {
+            tester!(goto_wait_stable_inner, "firefox")
+        }
+        #[test]
+        fn it_opens_new_tabs() {
+            tester!(new_tab_inner, "firefox")
+        }
+        #[test]
+        fn it_clicks_at_offsets() {
+            tester!(click_at_inner, "firefox")
+        }
+        #[test]
+        fn it_launches_with_profile() {
+            tester!(firefox_profile_inner, "firefox", |b| {
+                // the profile is packed up right away, so it is not needed past this point
+                let dir = write_test_profile();
+                b.firefox_profile(&dir).unwrap();
+                std::fs::remove_dir_all(&dir).unwrap();
+            })
+        }
+        #[test]
+        fn it_installs_addons() {
+            tester!(addon_inner, "firefox")
+        }
+        #[test]
+        fn it_switches_context() {
+            tester!(context_inner, "firefox")
+        }
+        #[test]
+        fn it_submits_and_waits() {
+            tester!(submit_and_wait_inner, "firefox")
+        }
+        #[test]
+        fn it_finds_element_from_point() {
+            tester!(element_from_point_inner, "firefox")
+        }
+        #[test]
+        fn it_scrolls() {
+            tester!(scroll_inner, "firefox")
+        }
+        #[test]
+        #[ignore]
+        fn it_reads_viewport_size() {
+            tester!(viewport_size_inner, "firefox")
+        }
+        #[test]
+        fn it_moves_with_offset() {
+            tester!(move_with_offset_inner, "firefox")
+        }
+        #[test]
+        fn it_clears_browser_state() {
+            tester!(clear_browser_state_inner, "firefox")
+        }
+        #[test]
+        fn it_waits_for_url_change() {
+            tester!(wait_for_url_change_inner, "firefox")
+        }
+        #[test]
+        fn it_reports_error_details() {
+            tester!(error_details_inner, "firefox")
+        }
+        #[test]
+        fn it_sends_keys_in_chunks() {
+            tester!(send_keys_chunked_inner, "firefox")
+        }
+        #[test]
+        fn it_reads_props_for_all() {
+            tester!(props_for_inner, "firefox")
+        }
+        #[test]
+        fn it_screenshots_after_goto() {
+            tester!(goto_screenshot_inner, "firefox")
+        }
+        #[test]
+        fn it_finds_with_compiled_locators() {
+            tester!(compiled_locator_inner, "firefox")
+        }
+        #[test]
+        fn it_reads_navigation_timing() {
+            tester!(navigation_timing_inner, "firefox")
+        }
+        #[test]
+        fn it_waits_until_enabled() {
+            tester!(wait_until_enabled_inner, "firefox")
+        }
+        #[test]
+        fn it_executes_async_scripts() {
+            tester!(execute_async_inner, "firefox")
+        }
+        #[test]
+        fn it_checks_element_existence() {
+            tester!(element_exists_inner, "firefox")
+        }
+        #[test]
+        fn it_sets_attributes() {
+            tester!(set_attribute_inner, "firefox")
+        }
+        #[test]
+        fn it_enters_frames_by_css() {
+            tester!(enter_frame_by_css_inner, "firefox")
+        }
+        #[test]
+        fn it_runs_in_frames() {
+            tester!(in_frame_inner, "firefox")
+        }
+        #[test]
+        fn it_saves_source() {
+            tester!(save_source_inner, "firefox")
+        }
+        #[test]
+        fn it_sets_implicit_wait() {
+            tester!(set_implicit_wait_inner, "firefox")
+        }
+        #[test]
+        fn it_reads_location_in_view() {
+            tester!(location_in_view_inner, "firefox")
+        }
+        #[test]
+        fn it_proxies_raw_requests() {
+            tester!(proxy_raw_requests_inner, "firefox", use_stub_proxy)
+        }
+        #[test]
+        fn it_reads_texts() {
+            tester!(texts_inner, "firefox")
+        }
+        #[test]
+        fn it_polls_without_blocking() {
+            tester!(poll_inner, "firefox")
+        }
+        #[test]
+        fn it_expects_tags() {
+            tester!(expect_tag_inner, "firefox")
+        }
+        #[test]
+        #[ignore]
+        fn it_minimizes_window() {
+            tester!(minimize_window_inner, "firefox")
+        }
+        #[test]
+        fn it_executes_typed_scripts() {
+            tester!(execute_typed_inner, "firefox")
+        }
+        #[test]
+        fn it_types_text() {
+            tester!(type_text_inner, "firefox")
+        }
+        #[test]
+        fn it_waits_for_navigation_with_timeout() {
+            tester!(wait_for_navigation_timeout_inner, "firefox")
+        }
+        #[test]
+        fn it_refills_inputs() {
+            tester!(refill_inner, "firefox")
+        }
+        #[test]
+        fn it_saves_element_screenshots() {
+            tester!(element_save_screenshot_inner, "firefox")
+        }
+        #[test]
+        fn it_saves_screenshots() {
+            tester!(save_screenshot_inner, "firefox")
+        }
+        #[test]
+        fn it_reads_computed_styles() {
+            tester!(computed_styles_inner, "firefox")
+        }
+        #[test]
+        fn it_checks_attributes() {
+            tester!(has_attr_inner, "firefox")
+        }
+        #[test]
+        #[ignore]
+        fn it_updates_window_rect() {
+            tester!(update_window_rect_inner, "firefox")
+        }
+        #[test]
+        fn it_compares_elements() {
+            tester!(equals_inner, "firefox")
+        }
+        #[test]
+        fn it_clears_editable_regions() {
+            tester!(clear_editable_inner, "firefox")
+        }
+        #[test]
+        fn it_waits_for_alerts() {
+            tester!(wait_for_alert_inner, "firefox")
+        }
+        #[test]
+        fn it_finds_window_with_title() {
+            tester!(window_with_title_inner, "firefox")
+        }
+        #[test]
+        fn it_checks_viewport_visibility() {
+            tester!(in_viewport_inner, "firefox")
+        }
+        #[test]
+        fn it_finds_limited_elements() {
+            tester!(find_all_limited_inner, "firefox")
+        }
+        #[test]
+        fn it_checks_focused_window() {
+            tester!(focused_window_valid_inner, "firefox")
         }
     }
 }
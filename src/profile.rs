@@ -0,0 +1,67 @@
+use std::fs;
+use std::io::{self, Cursor, Write};
+use std::path::Path;
+use zip;
+
+/// Files that Firefox uses to mark a profile as being in use.
+///
+/// Copying these along would make the browser think that the profile is already open elsewhere.
+const LOCK_FILES: &[&str] = &["parent.lock", "lock", ".parentlock"];
+
+/// Pack the profile directory at `path` into a zip archive, as expected by the `profile` entry
+/// of [`moz:firefoxOptions`].
+///
+/// [`moz:firefoxOptions`]: https://developer.mozilla.org/en-US/docs/Web/WebDriver/Capabilities/firefoxOptions
+pub(crate) fn zip_profile(path: &Path) -> io::Result<Vec<u8>> {
+    if !path.is_dir() {
+        return Err(io::Error::new(
+            io::ErrorKind::NotFound,
+            format!("{} is not a profile directory", path.display()),
+        ));
+    }
+
+    let mut zip = zip::ZipWriter::new(Cursor::new(Vec::new()));
+    add_dir(&mut zip, path, "").map_err(from_zip)?;
+    Ok(zip.finish().map_err(from_zip)?.into_inner())
+}
+
+fn add_dir(
+    zip: &mut zip::ZipWriter<Cursor<Vec<u8>>>,
+    dir: &Path,
+    prefix: &str,
+) -> zip::result::ZipResult<()> {
+    let options =
+        zip::write::FileOptions::default().compression_method(zip::CompressionMethod::Deflated);
+    for entry in fs::read_dir(dir)? {
+        let entry = entry?;
+        let name = entry.file_name();
+        let name = match name.to_str() {
+            Some(name) => name,
+            None => {
+                return Err(io::Error::new(
+                    io::ErrorKind::InvalidData,
+                    format!("{} is not valid UTF-8", entry.path().display()),
+                )
+                .into());
+            }
+        };
+
+        // zip archives always use forward slashes
+        let name = format!("{}{}", prefix, name);
+        if entry.file_type()?.is_dir() {
+            zip.add_directory(format!("{}/", name), options)?;
+            add_dir(zip, &entry.path(), &format!("{}/", name))?;
+        } else if !LOCK_FILES.contains(&&*name) {
+            zip.start_file(name, options)?;
+            zip.write_all(&fs::read(entry.path())?)?;
+        }
+    }
+    Ok(())
+}
+
+fn from_zip(e: zip::result::ZipError) -> io::Error {
+    match e {
+        zip::result::ZipError::Io(e) => e,
+        e => io::Error::new(io::ErrorKind::Other, e),
+    }
+}
@@ -6,15 +6,18 @@ use hyper_tls;
 use mime;
 use serde_json;
 use serde_json::Value as Json;
+use std::fmt;
 use std::io;
 use std::mem;
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
 use tokio;
 use tokio::prelude::*;
 use url;
 use webdriver;
-use webdriver::command::WebDriverCommand;
+use webdriver::command::{WebDriverCommand, WebDriverExtensionCommand};
 use webdriver::error::ErrorStatus;
-use webdriver::error::WebDriverError;
 use futures::future::{ok, err};
 
 type Ack = futures::sync::oneshot::Sender<Result<Json, error::CmdError>>;
@@ -24,20 +27,233 @@ type Ack = futures::sync::oneshot::Sender<Result<Json, error::CmdError>>;
 pub struct Client {
     tx: futures::sync::mpsc::UnboundedSender<Task>,
     legacy: bool,
+    /// Shared between clones, since restarting the session replaces the capabilities.
+    caps: Arc<Mutex<Option<Arc<webdriver::capabilities::Capabilities>>>>,
+    requested_caps: Option<Arc<webdriver::capabilities::Capabilities>>,
+    retry_stale: Arc<AtomicBool>,
+    proxy_raw: Arc<AtomicBool>,
+    /// Shared with the session task, which reads it when deciding whether to resend a command.
+    retry_reset: Arc<AtomicBool>,
 }
 
-type Wcmd = WebDriverCommand<webdriver::command::VoidWebDriverExtensionCommand>;
+/// WebDriver commands that are not part of the W3C specification, but that are supported by
+/// particular WebDriver implementations.
+#[derive(Clone, Debug, PartialEq)]
+pub(crate) enum ExtensionCommand {
+    /// Run a [Chrome DevTools Protocol] command through chromedriver.
+    ///
+    /// [Chrome DevTools Protocol]: https://chromedevtools.github.io/devtools-protocol/
+    ChromeCdp { cmd: String, params: Json },
+
+    /// Print the current page to PDF.
+    ///
+    /// This is part of the W3C specification, but is not yet known to the `webdriver` crate.
+    Print(Json),
+
+    /// Set the state of a permission, as defined by the [Permissions] specification.
+    ///
+    /// [Permissions]: https://w3c.github.io/permissions/#webdriver-command-set-permission
+    SetPermission(Json),
+
+    /// Get the computed WAI-ARIA role of an element.
+    ///
+    /// This is part of the W3C specification, but is not yet known to the `webdriver` crate.
+    GetComputedRole(webdriver::common::WebElement),
+
+    /// Get the computed accessible name of an element.
+    ///
+    /// This is part of the W3C specification, but is not yet known to the `webdriver` crate.
+    GetComputedLabel(webdriver::common::WebElement),
+
+    /// Take a screenshot of the entire document through geckodriver.
+    FirefoxFullScreenshot,
+
+    /// Open a new top-level browsing context (a tab or a window).
+    ///
+    /// This is part of the W3C specification, but is not yet known to the `webdriver` crate.
+    NewWindow(Json),
+
+    /// Install a Firefox add-on through geckodriver.
+    InstallAddon(Json),
+
+    /// Uninstall a Firefox add-on through geckodriver.
+    UninstallAddon(Json),
+
+    /// Get whether Firefox commands run in the content or the chrome context.
+    GetContext,
+
+    /// Set whether Firefox commands run in the content or the chrome context.
+    SetContext(Json),
+
+    /// Find the first element matching a locator, whose request body was serialized up front by
+    /// `Locator::compile`.
+    FindCompiled(Arc<str>),
+
+    /// Find all elements matching a locator, whose request body was serialized up front by
+    /// `Locator::compile`.
+    FindAllCompiled(Arc<str>),
+}
+
+impl ExtensionCommand {
+    fn endpoint(&self, base: &url::Url) -> Result<url::Url, url::ParseError> {
+        match *self {
+            ExtensionCommand::ChromeCdp { .. } => base.join("goog/cdp/execute"),
+            ExtensionCommand::Print(..) => base.join("print"),
+            ExtensionCommand::SetPermission(..) => base.join("permissions"),
+            ExtensionCommand::GetComputedRole(ref we) => {
+                base.join(&format!("element/{}/computedrole", we.id))
+            }
+            ExtensionCommand::GetComputedLabel(ref we) => {
+                base.join(&format!("element/{}/computedlabel", we.id))
+            }
+            ExtensionCommand::FirefoxFullScreenshot => base.join("moz/screenshot/full"),
+            ExtensionCommand::NewWindow(..) => base.join("window/new"),
+            ExtensionCommand::InstallAddon(..) => base.join("moz/addon/install"),
+            ExtensionCommand::UninstallAddon(..) => base.join("moz/addon/uninstall"),
+            ExtensionCommand::GetContext | ExtensionCommand::SetContext(..) => {
+                base.join("moz/context")
+            }
+            ExtensionCommand::FindCompiled(..) => base.join("element"),
+            ExtensionCommand::FindAllCompiled(..) => base.join("elements"),
+        }
+    }
+
+    fn method(&self) -> hyper::Method {
+        match *self {
+            ExtensionCommand::ChromeCdp { .. }
+            | ExtensionCommand::Print(..)
+            | ExtensionCommand::SetPermission(..)
+            | ExtensionCommand::NewWindow(..)
+            | ExtensionCommand::InstallAddon(..)
+            | ExtensionCommand::UninstallAddon(..)
+            | ExtensionCommand::SetContext(..)
+            | ExtensionCommand::FindCompiled(..)
+            | ExtensionCommand::FindAllCompiled(..) => hyper::Method::POST,
+            ExtensionCommand::GetComputedRole(..)
+            | ExtensionCommand::GetComputedLabel(..)
+            | ExtensionCommand::FirefoxFullScreenshot
+            | ExtensionCommand::GetContext => hyper::Method::GET,
+        }
+    }
+}
+
+impl WebDriverExtensionCommand for ExtensionCommand {
+    fn parameters_json(&self) -> Option<Json> {
+        match *self {
+            ExtensionCommand::ChromeCdp {
+                ref cmd,
+                ref params,
+            } => Some(serde_json::json!({ "cmd": cmd, "params": params })),
+            ExtensionCommand::Print(ref params)
+            | ExtensionCommand::SetPermission(ref params)
+            | ExtensionCommand::NewWindow(ref params)
+            | ExtensionCommand::InstallAddon(ref params)
+            | ExtensionCommand::UninstallAddon(ref params)
+            | ExtensionCommand::SetContext(ref params) => Some(params.clone()),
+            ExtensionCommand::FindCompiled(ref body)
+            | ExtensionCommand::FindAllCompiled(ref body) => serde_json::from_str(body).ok(),
+            ExtensionCommand::GetComputedRole(..)
+            | ExtensionCommand::GetComputedLabel(..)
+            | ExtensionCommand::FirefoxFullScreenshot
+            | ExtensionCommand::GetContext => None,
+        }
+    }
+}
+
+type Wcmd = WebDriverCommand<ExtensionCommand>;
+
+/// Read the status, content type, and body of the response to a request to the WebDriver server.
+fn read_response(
+    req: hyper::client::ResponseFuture,
+) -> impl Future<Item = (hyper::Chunk, Option<mime::Mime>, hyper::StatusCode), Error = error::CmdError>
+{
+    req.map_err(error::CmdError::from).and_then(move |res| {
+        // keep track of result status (.body() consumes self -- ugh)
+        let status = res.status();
+
+        // check that the server sent us json
+        let ctype = res
+            .headers()
+            .get(hyper::header::CONTENT_TYPE)
+            .and_then(|ctype| ctype.to_str().ok()?.parse::<mime::Mime>().ok());
+
+        // What did the server send us?
+        res.into_body()
+            .concat2()
+            .map(move |body| (body, ctype, status))
+            .map_err(|e| -> error::CmdError { e.into() })
+    })
+}
+
+/// Returns true if `e` means that the connection to the WebDriver server was reset or closed
+/// before we got a complete response.
+fn is_connection_reset(e: &error::CmdError) -> bool {
+    let e = match *e {
+        error::CmdError::Failed(ref e) => e,
+        _ => return false,
+    };
+    if e.is_incomplete_message() {
+        return true;
+    }
+
+    let mut cause = std::error::Error::source(e);
+    while let Some(c) = cause {
+        if let Some(e) = c.downcast_ref::<io::Error>() {
+            return match e.kind() {
+                io::ErrorKind::ConnectionReset
+                | io::ErrorKind::ConnectionAborted
+                | io::ErrorKind::BrokenPipe => true,
+                _ => false,
+            };
+        }
+        cause = std::error::Error::source(c);
+    }
+    false
+}
+
+fn new_session_cmd(cap: webdriver::capabilities::Capabilities, legacy: bool) -> Wcmd {
+    let params = if legacy {
+        webdriver::command::NewSessionParameters::Legacy(
+            webdriver::capabilities::LegacyNewSessionParameters {
+                desired: cap,
+                required: webdriver::capabilities::Capabilities::new(),
+            },
+        )
+    } else {
+        webdriver::command::NewSessionParameters::Spec(
+            webdriver::capabilities::SpecNewSessionParameters {
+                alwaysMatch: cap,
+                firstMatch: vec![webdriver::capabilities::Capabilities::new()],
+            },
+        )
+    };
+    WebDriverCommand::NewSession(params)
+}
+
+/// A function that is told about every WebDriver command once it completes.
+#[derive(Clone)]
+pub(crate) struct Observer(pub(crate) Arc<dyn Fn(&str, Duration) + Send + Sync>);
+
+impl fmt::Debug for Observer {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str("Observer")
+    }
+}
 
 #[derive(Debug)]
 pub(crate) enum Cmd {
     SetUA(String),
+    SetObserver(Observer),
     GetSessionId,
+    EndSession,
     Shutdown,
     Persist,
     GetUA,
     Raw {
         req: hyper::Request<hyper::Body>,
         rsp: futures::sync::oneshot::Sender<Result<hyper::Response<hyper::Body>, hyper::Error>>,
+        /// The `(host, port)` of the HTTP proxy to send the request through, if any.
+        proxy: Option<(String, u16)>,
     },
     WebDriver(Wcmd),
 }
@@ -88,6 +304,86 @@ impl Client {
     pub(crate) fn is_legacy(&self) -> bool {
         self.legacy
     }
+
+    /// The capabilities the WebDriver server reported when the session was created.
+    ///
+    /// This is `None` if the server did not report any, or if we attached to an existing session.
+    pub(crate) fn capabilities(&self) -> Option<Arc<webdriver::capabilities::Capabilities>> {
+        self.caps.lock().unwrap().clone()
+    }
+
+    /// The capabilities this client asked for when it created its session, if it did.
+    pub(crate) fn requested_capabilities(&self) -> Option<&webdriver::capabilities::Capabilities> {
+        self.requested_caps.as_ref().map(|caps| &**caps)
+    }
+
+    /// Returns true if element commands that hit a stale element should find it again and retry.
+    pub(crate) fn retries_stale_elements(&self) -> bool {
+        self.retry_stale.load(Ordering::SeqCst)
+    }
+
+    pub(crate) fn set_retry_stale_elements(&self, retry: bool) {
+        self.retry_stale.store(retry, Ordering::SeqCst);
+    }
+
+    /// Returns true if raw HTTP requests should go through the session's proxy, if it has one.
+    pub(crate) fn proxies_raw_requests(&self) -> bool {
+        self.proxy_raw.load(Ordering::SeqCst)
+    }
+
+    pub(crate) fn set_proxy_raw_requests(&self, proxy: bool) {
+        self.proxy_raw.store(proxy, Ordering::SeqCst);
+    }
+
+    pub(crate) fn set_retry_on_reset(&self, retry: bool) {
+        self.retry_reset.store(retry, Ordering::SeqCst);
+    }
+
+    /// The command that creates a new session with the capabilities this client originally asked
+    /// for, if this client created its own session.
+    pub(crate) fn new_session_cmd(&self) -> Option<Wcmd> {
+        self.requested_caps
+            .as_ref()
+            .map(|cap| new_session_cmd((**cap).clone(), self.legacy))
+    }
+
+    pub(crate) fn set_capabilities(&self, caps: Option<webdriver::capabilities::Capabilities>) {
+        *self.caps.lock().unwrap() = caps.map(Arc::new);
+    }
+
+    /// Returns true if the browser is running on macOS.
+    pub(crate) fn is_mac(&self) -> bool {
+        self.capabilities()
+            .and_then(|caps| {
+                caps.get("platformName")
+                    .and_then(|name| name.as_str())
+                    // e.g., "mac" or "macos", but ChromeDriver reports "mac os x"
+                    .map(|name| name.to_ascii_lowercase().starts_with("mac"))
+            })
+            .unwrap_or(false)
+    }
+
+    /// Returns true if the session is driving a Chromium-based browser.
+    pub(crate) fn is_chromium(&self) -> bool {
+        self.capabilities()
+            .and_then(|caps| {
+                caps.get("browserName")
+                    .and_then(|name| name.as_str())
+                    .map(|name| name == "chrome" || name == "chromium" || name == "msedge")
+            })
+            .unwrap_or(false)
+    }
+
+    /// Returns true if the session is driving Firefox.
+    pub(crate) fn is_firefox(&self) -> bool {
+        self.capabilities()
+            .and_then(|caps| {
+                caps.get("browserName")
+                    .and_then(|name| name.as_str())
+                    .map(|name| name == "firefox")
+            })
+            .unwrap_or(false)
+    }
 }
 
 enum Ongoing {
@@ -139,7 +435,14 @@ impl Ongoing {
                 }
                 OngoingResult::Break
             }
-            Ongoing::WebDriver { mut fut, ack } => {
+            Ongoing::WebDriver { mut fut, mut ack } => {
+                if !try_extract_session {
+                    if let Ok(Async::Ready(())) = ack.poll_cancel() {
+                        // nobody is waiting for the response any more (e.g., because the caller
+                        // gave up after a deadline), so abandon the request
+                        return Ok(Async::Ready(OngoingResult::Continue));
+                    }
+                }
                 let rsp = match fut.poll() {
                     Ok(Async::NotReady) => {
                         mem::replace(self, Ongoing::WebDriver { fut, ack });
@@ -193,7 +496,44 @@ pub(crate) struct Session {
     session: Option<String>,
     legacy: bool,
     ua: Option<String>,
+    headers: hyper::HeaderMap,
+    retry_on_reset: Arc<AtomicBool>,
+    observer: Option<Observer>,
     persist: bool,
+    frame_depth: Arc<AtomicUsize>,
+    proxied: Option<((String, u16), hyper::Client<ProxyConnector, hyper::Body>)>,
+}
+
+/// Connects to an HTTP proxy rather than to the host a request is for, so that the request is
+/// sent through that proxy.
+///
+/// Only plain HTTP requests can be proxied this way, since HTTPS would need a `CONNECT` tunnel.
+#[derive(Clone)]
+struct ProxyConnector {
+    http: hyper::client::HttpConnector,
+    host: String,
+    port: u16,
+}
+
+impl hyper::client::connect::Connect for ProxyConnector {
+    type Transport = <hyper::client::HttpConnector as hyper::client::connect::Connect>::Transport;
+    type Error = io::Error;
+    type Future = Box<
+        dyn Future<Item = (Self::Transport, hyper::client::connect::Connected), Error = io::Error>
+            + Send,
+    >;
+
+    fn connect(&self, mut dst: hyper::client::connect::Destination) -> Self::Future {
+        if let Err(e) = dst.set_host(&self.host) {
+            return Box::new(err(io::Error::new(io::ErrorKind::InvalidInput, e)));
+        }
+        dst.set_port(self.port);
+        // marking the connection as proxied makes hyper send the full URL in the request line
+        Box::new(
+            hyper::client::connect::Connect::connect(&self.http, dst)
+                .map(|(io, connected)| (io, connected.proxy(true))),
+        )
+    }
 }
 
 impl Future for Session {
@@ -228,21 +568,48 @@ impl Future for Session {
                         self.ua = Some(ua);
                         let _ = ack.send(Ok(Json::Null));
                     }
+                    Cmd::SetObserver(observer) => {
+                        self.observer = Some(observer);
+                        let _ = ack.send(Ok(Json::Null));
+                    }
                     Cmd::GetUA => {
                         let _ =
                             ack.send(Ok(self.ua.clone().map(Json::String).unwrap_or(Json::Null)));
                     }
-                    Cmd::Raw { req, rsp } => {
+                    Cmd::Raw { req, rsp, proxy } => {
+                        let fut = match proxy {
+                            Some(proxy) => self.proxied_client(proxy).request(req),
+                            None => self.c.request(req),
+                        };
                         self.ongoing = Ongoing::Raw {
                             ack: ack,
                             ret: rsp,
-                            fut: self.c.request(req),
+                            fut,
                         };
                     }
                     Cmd::Persist => {
                         self.persist = true;
                         let _ = ack.send(Ok(Json::Null));
                     }
+                    Cmd::EndSession => {
+                        // tear down the browser session, but keep serving commands so that a new
+                        // session can be created in its place
+                        match self.session.take() {
+                            Some(session) => {
+                                self.ongoing = Ongoing::WebDriver {
+                                    ack,
+                                    fut: Box::new(
+                                        self.delete_session(&session)
+                                            .map(|_| Json::Null)
+                                            .map_err(error::CmdError::from),
+                                    ),
+                                };
+                            }
+                            None => {
+                                let _ = ack.send(Ok(Json::Null));
+                            }
+                        }
+                    }
                     Cmd::Shutdown => {
                         // explicit client shutdown
                         self.shutdown(Some(ack));
@@ -258,7 +625,7 @@ impl Future for Session {
                         }
                         self.ongoing = Ongoing::WebDriver {
                             ack,
-                            fut: Box::new(self.issue_wd_cmd(request)),
+                            fut: self.issue_tracked_wd_cmd(request),
                         };
                     }
                 };
@@ -278,25 +645,34 @@ impl Future for Session {
 
 impl Session {
     fn shutdown(&mut self, ack: Option<Ack>) {
-        let url = {
-            self.wdb
-                .join(&format!("session/{}", self.session.as_ref().unwrap()))
-                .unwrap()
+        // if the server never gave us a session (e.g., because it refused to create one), there is
+        // nothing to tear down
+        let fut = match self.session {
+            Some(ref session) => self.delete_session(session),
+            None => {
+                if let Some(ack) = ack {
+                    let _ = ack.send(Ok(Json::Null));
+                }
+                self.ongoing = Ongoing::Break;
+                return;
+            }
         };
 
-        self.ongoing = Ongoing::Shutdown {
-            ack,
-            fut: self.c.request(
-                hyper::Request::delete(url.as_str())
-                    .body(hyper::Body::empty())
-                    .unwrap(),
-            ),
-        };
+        self.ongoing = Ongoing::Shutdown { ack, fut };
+    }
+
+    fn delete_session(&self, session: &str) -> hyper::client::ResponseFuture {
+        let url = self.wdb.join(&format!("session/{}", session)).unwrap();
+        let mut req = hyper::Request::delete(url.as_str());
+        for (name, value) in &self.headers {
+            req.header(name, value);
+        }
+        self.c.request(req.body(hyper::Body::empty()).unwrap())
     }
 
     fn map_handshake_response(
         response: Result<Json, error::CmdError>,
-    ) -> Result<(), error::NewSessionError> {
+    ) -> Result<Option<webdriver::capabilities::Capabilities>, error::NewSessionError> {
         match response {
             Ok(Json::Object(mut v)) => {
                 // TODO: not all impls are w3c compatible
@@ -305,7 +681,13 @@ impl Session {
                 // NOTE: remove so we can re-insert and return if something's wrong
                 if let Some(session_id) = v.remove("sessionId") {
                     if session_id.is_string() {
-                        return Ok(());
+                        // w3c puts the negotiated capabilities under "capabilities",
+                        // while legacy implementations put them under "value"
+                        let caps = v.remove("capabilities").or_else(|| v.remove("value"));
+                        return Ok(match caps {
+                            Some(Json::Object(caps)) => Some(caps),
+                            _ => None,
+                        });
                     }
                     v.insert("sessionId".to_string(), session_id);
                     Err(error::NewSessionError::NotW3C(Json::Object(v)))
@@ -320,11 +702,16 @@ impl Session {
                 Err(error::NewSessionError::NotW3C(Json::String(v)))
             }
             Err(error::CmdError::Standard(
-                e @ WebDriverError {
+                e @ error::WebDriverError {
                     error: ErrorStatus::SessionNotCreated,
                     ..
                 },
-            )) => Err(error::NewSessionError::SessionNotCreated(e)),
+            )) => Err(error::NewSessionError::SessionNotCreated {
+                error: e.error,
+                message: e.message,
+                stacktrace: e.stacktrace,
+            }),
+            Err(error::CmdError::Standard(e)) => Err(error::NewSessionError::Standard(e)),
             Err(e) => {
                 panic!("unexpected webdriver error; {}", e);
             }
@@ -334,6 +721,7 @@ impl Session {
     pub(crate) fn with_session_id(
         webdriver: &str,
         session_id: String,
+        caps: Option<webdriver::capabilities::Capabilities>,
     ) -> impl Future<Item = Client, Error = error::NewSessionError> {
         // Where is the WebDriver server?
         let wdb = match webdriver.parse::<url::Url>() {
@@ -349,6 +737,7 @@ impl Session {
 
         // We're going to need a channel for sending requests to the WebDriver host
         let (tx, rx) = futures::sync::mpsc::unbounded();
+        let retry_reset = Arc::new(AtomicBool::new(false));
 
         // Set up our WebDriver session.
         // We don't want to call tokio::spawn directly here, because we may not yet be executing
@@ -363,13 +752,23 @@ impl Session {
                 session: Some(session_id),
                 legacy: false,
                 ua: None,
+                headers: hyper::HeaderMap::new(),
+                retry_on_reset: retry_reset.clone(),
+                observer: None,
                 persist: false,
+                frame_depth: Arc::new(AtomicUsize::new(0)),
+                proxied: None,
             });
 
             // now that the session is running, let's do the handshake
             let mut client = Client {
                 tx: tx.clone(),
                 legacy: false,
+                caps: Arc::new(Mutex::new(caps.map(Arc::new))),
+                requested_caps: None,
+                retry_stale: Arc::new(AtomicBool::new(false)),
+                proxy_raw: Arc::new(AtomicBool::new(false)),
+                retry_reset,
             };
 
             client.current_url().then(|res| {
@@ -384,6 +783,9 @@ impl Session {
     pub(crate) fn with_capabilities(
         webdriver: &str,
         mut cap: webdriver::capabilities::Capabilities,
+        headers: hyper::HeaderMap,
+        ua: Option<String>,
+        protocol: Option<::Protocol>,
     ) -> impl Future<Item = Client, Error = error::NewSessionError> {
         // Where is the WebDriver server?
         let wdb = match webdriver.parse::<url::Url>() {
@@ -399,6 +801,7 @@ impl Session {
 
         // We're going to need a channel for sending requests to the WebDriver host
         let (tx, rx) = futures::sync::mpsc::unbounded();
+        let retry_reset = Arc::new(AtomicBool::new(false));
 
         // Set up our WebDriver session.
         // We don't want to call tokio::spawn directly here, because we may not yet be executing
@@ -412,14 +815,24 @@ impl Session {
                 wdb: wdb,
                 session: None,
                 legacy: false,
-                ua: None,
+                ua,
+                headers,
+                retry_on_reset: retry_reset.clone(),
+                observer: None,
                 persist: false,
+                frame_depth: Arc::new(AtomicUsize::new(0)),
+                proxied: None,
             });
 
             // now that the session is running, let's do the handshake
             let mut client = Client {
                 tx: tx.clone(),
                 legacy: false,
+                caps: Arc::new(Mutex::new(None)),
+                requested_caps: None,
+                retry_stale: Arc::new(AtomicBool::new(false)),
+                proxy_raw: Arc::new(AtomicBool::new(false)),
+                retry_reset: retry_reset.clone(),
             };
 
             // Create a new session for this client
@@ -428,23 +841,23 @@ impl Session {
             //  - we want the browser to wait for the page to load
             cap.insert("pageLoadStrategy".to_string(), Json::from("normal"));
 
+            // if the user told us the server only speaks the legacy protocol, go straight to it
+            let legacy_only = protocol == Some(::Protocol::Legacy);
+
             // make chrome comply with w3c
-            cap.entry("goog:chromeOptions".to_string())
-                .or_insert_with(|| Json::Object(serde_json::Map::new()))
-                .as_object_mut()
-                .expect("goog:chromeOptions wasn't a JSON object")
-                .insert("w3c".to_string(), Json::from(true));
-
-            let session_config = webdriver::capabilities::SpecNewSessionParameters {
-                alwaysMatch: cap.clone(),
-                firstMatch: vec![webdriver::capabilities::Capabilities::new()],
-            };
-            let spec = webdriver::command::NewSessionParameters::Spec(session_config);
+            if !legacy_only {
+                cap.entry("goog:chromeOptions".to_string())
+                    .or_insert_with(|| Json::Object(serde_json::Map::new()))
+                    .as_object_mut()
+                    .expect("goog:chromeOptions wasn't a JSON object")
+                    .insert("w3c".to_string(), Json::from(true));
+            }
 
+            let requested_caps = Arc::new(cap.clone());
             client
-                .issue(WebDriverCommand::NewSession(spec))
+                .issue(new_session_cmd(cap.clone(), legacy_only))
                 .then(Self::map_handshake_response)
-                .map(|_| false)
+                .map(move |caps| (legacy_only, caps))
                 .or_else(move |e| {
                     // maybe try legacy mode?
                     match e {
@@ -472,25 +885,17 @@ impl Session {
                                 _ => {}
                             }
 
-                            if legacy {
+                            // (unless the user picked a protocol, in which case we stick with it)
+                            if legacy && protocol.is_none() {
                                 // we're dealing with an implementation that only supports the legacy
                                 // WebDriver protocol:
                                 // https://github.com/SeleniumHQ/selenium/wiki/JsonWireProtocol
-                                let session_config =
-                                    webdriver::capabilities::LegacyNewSessionParameters {
-                                        desired: cap,
-                                        required: webdriver::capabilities::Capabilities::new(),
-                                    };
-                                let spec = webdriver::command::NewSessionParameters::Legacy(
-                                    session_config,
-                                );
-
                                 // try again with a legacy client
                                 future::Either::A(
                                     client
-                                        .issue(WebDriverCommand::NewSession(spec))
+                                        .issue(new_session_cmd(cap, true))
                                         .then(Self::map_handshake_response)
-                                        .map(|_| true),
+                                        .map(|caps| (true, caps)),
                                 )
                             } else {
                                 future::Either::B(future::err(error::NewSessionError::NotW3C(json)))
@@ -499,10 +904,40 @@ impl Session {
                         e => future::Either::B(future::err(e)),
                     }
                 })
-                .map(move |legacy| Client { tx, legacy })
+                .map(move |(legacy, caps)| Client {
+                    tx,
+                    legacy,
+                    caps: Arc::new(Mutex::new(caps.map(Arc::new))),
+                    requested_caps: Some(requested_caps),
+                    retry_stale: Arc::new(AtomicBool::new(false)),
+                    proxy_raw: Arc::new(AtomicBool::new(false)),
+                    retry_reset,
+                })
         }))
     }
 
+    /// The client to use for raw requests that go through the HTTP proxy at `proxy`.
+    ///
+    /// The client is kept around, so that connections to the proxy can be reused.
+    fn proxied_client(
+        &mut self,
+        proxy: (String, u16),
+    ) -> &hyper::Client<ProxyConnector, hyper::Body> {
+        let reuse = match self.proxied {
+            Some((ref current, _)) => *current == proxy,
+            None => false,
+        };
+        if !reuse {
+            let connector = ProxyConnector {
+                http: hyper::client::HttpConnector::new(1),
+                host: proxy.0.clone(),
+                port: proxy.1,
+            };
+            self.proxied = Some((proxy, hyper::Client::builder().build(connector)));
+        }
+        &self.proxied.as_ref().unwrap().1
+    }
+
     /// Helper for determining what URL endpoint to use for various requests.
     ///
     /// This mapping is essentially that of https://www.w3.org/TR/webdriver/#list-of-endpoints.
@@ -522,14 +957,20 @@ impl Session {
             WebDriverCommand::GoBack => base.join("back"),
             WebDriverCommand::Refresh => base.join("refresh"),
             WebDriverCommand::GetPageSource => base.join("source"),
+            WebDriverCommand::GetTitle => base.join("title"),
             WebDriverCommand::FindElement(..) => base.join("element"),
             WebDriverCommand::FindElements(..) => base.join("elements"),
-            WebDriverCommand::GetCookies => base.join("cookie"),
+            WebDriverCommand::GetCookies | WebDriverCommand::DeleteCookies => base.join("cookie"),
             WebDriverCommand::ExecuteScript(..) if self.legacy => base.join("execute"),
             WebDriverCommand::ExecuteScript(..) => base.join("execute/sync"),
+            WebDriverCommand::ExecuteAsyncScript(..) if self.legacy => base.join("execute_async"),
+            WebDriverCommand::ExecuteAsyncScript(..) => base.join("execute/async"),
             WebDriverCommand::IsDisplayed(ref we) => {
                 base.join(&format!("element/{}/displayed", we.id))
             },
+            WebDriverCommand::IsEnabled(ref we) => {
+                base.join(&format!("element/{}/enabled", we.id))
+            }
             WebDriverCommand::GetElementProperty(ref we, ref prop) => {
                 base.join(&format!("element/{}/property/{}", we.id, prop))
             }
@@ -551,16 +992,90 @@ impl Session {
             WebDriverCommand::GetElementText(ref we) => {
                 base.join(&format!("element/{}/text", we.id))
             }
+            WebDriverCommand::GetElementTagName(ref we) => {
+                base.join(&format!("element/{}/name", we.id))
+            }
             WebDriverCommand::ElementSendKeys(ref we, _) => {
                 base.join(&format!("element/{}/value", we.id))
             }
             WebDriverCommand::SetWindowRect(..) => base.join("window/rect"),
             WebDriverCommand::GetWindowRect => base.join("window/rect"),
+            WebDriverCommand::MinimizeWindow => base.join("window/minimize"),
             WebDriverCommand::TakeScreenshot => base.join("screenshot"),
+            WebDriverCommand::TakeElementScreenshot(ref we) => {
+                base.join(&format!("element/{}/screenshot", we.id))
+            }
+            WebDriverCommand::GetWindowHandle
+            | WebDriverCommand::CloseWindow
+            | WebDriverCommand::SwitchToWindow(..) => base.join("window"),
+            WebDriverCommand::GetWindowHandles => base.join("window/handles"),
+            WebDriverCommand::SwitchToFrame(..) => base.join("frame"),
+            WebDriverCommand::SwitchToParentFrame => base.join("frame/parent"),
+            WebDriverCommand::PerformActions(..) => base.join("actions"),
+            WebDriverCommand::SetTimeouts(..) => base.join("timeouts"),
+            WebDriverCommand::GetAlertText => base.join("alert/text"),
+            WebDriverCommand::Extension(ref ext) => ext.endpoint(&base),
             _ => unimplemented!(),
         }
     }
 
+    /// Issue a WebDriver command while keeping track of how deeply nested in frames we are.
+    ///
+    /// The frame depth is only used to give better diagnostics: if an element lookup fails while
+    /// we are inside a frame, the error message says so, since forgetting to leave a frame is a
+    /// common reason for elements not being found.
+    fn issue_tracked_wd_cmd(
+        &mut self,
+        cmd: Wcmd,
+    ) -> Box<dyn Future<Item = Json, Error = error::CmdError> + Send> {
+        enum FrameChange {
+            Enter,
+            Parent,
+            Top,
+        }
+
+        let change = match cmd {
+            WebDriverCommand::SwitchToFrame(ref params) if params.id.is_some() => {
+                Some(FrameChange::Enter)
+            }
+            WebDriverCommand::SwitchToParentFrame => Some(FrameChange::Parent),
+            // navigating and switching windows both take us back to the top-level context
+            WebDriverCommand::SwitchToFrame(..)
+            | WebDriverCommand::NewSession(..)
+            | WebDriverCommand::Get(..)
+            | WebDriverCommand::GoBack
+            | WebDriverCommand::Refresh
+            | WebDriverCommand::SwitchToWindow(..) => Some(FrameChange::Top),
+            _ => None,
+        };
+
+        let depth = self.frame_depth.clone();
+        Box::new(self.issue_wd_cmd(cmd).then(move |res| match res {
+            Ok(v) => {
+                let d = depth.load(Ordering::SeqCst);
+                match change {
+                    Some(FrameChange::Enter) => depth.store(d + 1, Ordering::SeqCst),
+                    Some(FrameChange::Parent) => depth.store(d.saturating_sub(1), Ordering::SeqCst),
+                    Some(FrameChange::Top) => depth.store(0, Ordering::SeqCst),
+                    None => {}
+                }
+                Ok(v)
+            }
+            Err(error::CmdError::NoSuchElement(mut e)) => {
+                let d = depth.load(Ordering::SeqCst);
+                if d != 0 {
+                    e.message = format!(
+                        "{} (note: the session is {} frame(s) deep; \
+                         use `switch_to_default_content` to return to the top-level document)",
+                        e.message, d
+                    );
+                }
+                Err(error::CmdError::NoSuchElement(e))
+            }
+            Err(e) => Err(e),
+        }))
+    }
+
     /// Helper for issuing a WebDriver command, and then reading and parsing the response.
     ///
     /// Since most `WebDriverCommand` arguments can already be turned directly into JSON, this is
@@ -570,7 +1085,7 @@ impl Session {
     /// [the spec]: https://www.w3.org/TR/webdriver/#list-of-endpoints
     fn issue_wd_cmd(
         &mut self,
-        cmd: WebDriverCommand<webdriver::command::VoidWebDriverExtensionCommand>,
+        cmd: Wcmd,
     ) -> impl Future<Item = Json, Error = error::CmdError> {
         use webdriver::command;
 
@@ -579,6 +1094,10 @@ impl Session {
             Ok(url) => url,
             Err(e) => return future::Either::B(future::err(error::CmdError::from(e))),
         };
+        let observed = self
+            .observer
+            .clone()
+            .map(|observer| (observer, self.endpoint_name(&url), Instant::now()));
         use hyper::Method;
         let mut method = Method::GET;
         let mut body = None;
@@ -623,7 +1142,8 @@ impl Session {
                 body = Some(serde_json::to_string(loc).unwrap());
                 method = Method::POST;
             }
-            WebDriverCommand::ExecuteScript(ref script) => {
+            WebDriverCommand::ExecuteScript(ref script)
+            | WebDriverCommand::ExecuteAsyncScript(ref script) => {
                 body = Some(serde_json::to_string(script).unwrap());
                 method = Method::POST;
             }
@@ -631,10 +1151,31 @@ impl Session {
                 body = Some(serde_json::to_string(keys).unwrap());
                 method = Method::POST;
             }
+            WebDriverCommand::SwitchToFrame(ref params) => {
+                body = Some(serde_json::to_string(params).unwrap());
+                method = Method::POST;
+            }
+            WebDriverCommand::SwitchToWindow(ref params) => {
+                body = Some(serde_json::to_string(params).unwrap());
+                method = Method::POST;
+            }
+            WebDriverCommand::PerformActions(ref params) => {
+                body = Some(serde_json::to_string(params).unwrap());
+                method = Method::POST;
+            }
+            WebDriverCommand::SetTimeouts(ref params) => {
+                body = Some(serde_json::to_string(params).unwrap());
+                method = Method::POST;
+            }
+            WebDriverCommand::CloseWindow | WebDriverCommand::DeleteCookies => {
+                method = Method::DELETE;
+            }
             WebDriverCommand::ElementClick(..)
             | WebDriverCommand::ElementClear(..)
             | WebDriverCommand::GoBack
-            | WebDriverCommand::Refresh => {
+            | WebDriverCommand::Refresh
+            | WebDriverCommand::MinimizeWindow
+            | WebDriverCommand::SwitchToParentFrame => {
                 body = Some("{}".to_string());
                 method = Method::POST;
             }
@@ -642,15 +1183,33 @@ impl Session {
                 body = Some(serde_json::to_string(params).unwrap());
                 method = Method::POST;
             }
+            WebDriverCommand::Extension(ExtensionCommand::FindCompiled(ref locator))
+            | WebDriverCommand::Extension(ExtensionCommand::FindAllCompiled(ref locator)) => {
+                // the body was serialized once, when the locator was compiled
+                body = Some(locator.to_string());
+                method = Method::POST;
+            }
+            WebDriverCommand::Extension(ref ext) => {
+                body = ext
+                    .parameters_json()
+                    .map(|params| serde_json::to_string(&params).unwrap());
+                method = ext.method();
+            }
             _ => {}
         }
 
+        // only commands that merely read state are safe to send again
+        let retry = self.retry_on_reset.load(Ordering::SeqCst) && method == Method::GET;
+
         // issue the command to the webdriver server
         let mut req = hyper::Request::builder();
         req.method(method).uri(url.as_str());
         if let Some(ref s) = self.ua {
             req.header(hyper::header::USER_AGENT, s.to_owned());
         }
+        for (name, value) in &self.headers {
+            req.header(name, value);
+        }
         // because https://github.com/hyperium/hyper/pull/727
         if !url.username().is_empty() || url.password().is_some() {
             req.header(
@@ -666,33 +1225,43 @@ impl Session {
             );
         }
 
-        let req = if let Some(body) = body.take() {
+        let body = body.take();
+        if let Some(ref body) = body {
             req.header(hyper::header::CONTENT_TYPE, mime::APPLICATION_JSON.as_ref());
             req.header(hyper::header::CONTENT_LENGTH, body.len());
-            self.c.request(req.body(body.into()).unwrap())
+        }
+        let template = req.body(()).unwrap();
+
+        // hyper consumes the request, so we need a fresh one every time we (re)send it
+        let client = self.c.clone();
+        let send = move || {
+            let body = match body {
+                Some(ref body) => hyper::Body::from(body.clone()),
+                None => hyper::Body::empty(),
+            };
+            let mut req = hyper::Request::new(body);
+            *req.method_mut() = template.method().clone();
+            *req.uri_mut() = template.uri().clone();
+            *req.headers_mut() = template.headers().clone();
+            read_response(client.request(req))
+        };
+
+        let rsp = send();
+        let rsp = if retry {
+            future::Either::A(rsp.or_else(move |e| {
+                if is_connection_reset(&e) {
+                    // hyper does not reuse broken connections, so this connects afresh
+                    future::Either::A(send())
+                } else {
+                    future::Either::B(future::err(e))
+                }
+            }))
         } else {
-            self.c.request(req.body(hyper::Body::empty()).unwrap())
+            future::Either::B(rsp)
         };
 
         let legacy = self.legacy;
-        let f = req
-            .map_err(error::CmdError::from)
-            .and_then(move |res| {
-                // keep track of result status (.body() consumes self -- ugh)
-                let status = res.status();
-
-                // check that the server sent us json
-                let ctype = res
-                    .headers()
-                    .get(hyper::header::CONTENT_TYPE)
-                    .and_then(|ctype| ctype.to_str().ok()?.parse::<mime::Mime>().ok());
-
-                // What did the server send us?
-                res.into_body()
-                    .concat2()
-                    .map(move |body| (body, ctype, status))
-                    .map_err(|e| -> error::CmdError { e.into() })
-            })
+        let f = rsp
             .and_then(|(body, ctype, status)| {
                 // Too bad we can't stream into a String :(
                 let body =
@@ -811,14 +1380,18 @@ impl Session {
                             "no such frame" => ErrorStatus::NoSuchFrame,
                             "no such window" => ErrorStatus::NoSuchWindow,
                             "stale element reference" => ErrorStatus::StaleElementReference,
-                            _ => unreachable!(),
+                            _ => ErrorStatus::UnknownError,
                         },
                         StatusCode::NOT_FOUND => match error {
                             "unknown command" => ErrorStatus::UnknownCommand,
                             "no such cookie" => ErrorStatus::NoSuchCookie,
                             "invalid session id" => ErrorStatus::InvalidSessionId,
+                            "no such alert" => ErrorStatus::NoSuchAlert,
                             "no such element" => ErrorStatus::NoSuchElement,
-                            _ => unreachable!(),
+                            "no such frame" => ErrorStatus::NoSuchFrame,
+                            "no such window" => ErrorStatus::NoSuchWindow,
+                            "stale element reference" => ErrorStatus::StaleElementReference,
+                            _ => ErrorStatus::UnknownError,
                         },
                         StatusCode::INTERNAL_SERVER_ERROR => match error {
                             "javascript error" => ErrorStatus::JavascriptError,
@@ -829,25 +1402,53 @@ impl Session {
                             "unexpected alert open" => ErrorStatus::UnexpectedAlertOpen,
                             "unknown error" => ErrorStatus::UnknownError,
                             "unsupported operation" => ErrorStatus::UnsupportedOperation,
-                            _ => unreachable!(),
+                            _ => ErrorStatus::UnknownError,
                         },
                         StatusCode::REQUEST_TIMEOUT => match error {
                             "timeout" => ErrorStatus::Timeout,
                             "script timeout" => ErrorStatus::ScriptTimeout,
-                            _ => unreachable!(),
+                            _ => ErrorStatus::UnknownError,
                         },
                         StatusCode::METHOD_NOT_ALLOWED => match error {
                             "unknown method" => ErrorStatus::UnknownMethod,
-                            _ => unreachable!(),
+                            _ => ErrorStatus::UnknownError,
                         },
-                        _ => unreachable!(),
+                        // e.g., a gateway in front of the server that rejects the request
+                        _ => ErrorStatus::UnknownError,
                     }
                 };
 
                 let message = body["message"].as_str().unwrap().to_string();
-                Err(error::CmdError::from(WebDriverError::new(es, message)))
+                let mut e = error::WebDriverError::new(es, message);
+                if let Some(stacktrace) = body.get("stacktrace").and_then(|s| s.as_str()) {
+                    e.stacktrace = stacktrace.to_string();
+                }
+                e.data = body.get("data").cloned();
+                Err(error::CmdError::from(e))
+            })
+            .then(move |r| {
+                if let Some((observer, endpoint, start)) = observed {
+                    (observer.0)(&endpoint, start.elapsed());
+                }
+                r
             });
 
         future::Either::A(f)
     }
+
+    /// A name for the endpoint at `url` that is suitable for grouping commands (e.g., in metrics).
+    ///
+    /// This is the path relative to the session, with any element id replaced by a placeholder,
+    /// such as `element/{element id}/click`.
+    fn endpoint_name(&self, url: &url::Url) -> String {
+        let path = url.path().trim_start_matches(self.wdb.path());
+        let mut segments: Vec<_> = path.split('/').collect();
+        if segments.len() > 2 && segments[0] == "session" {
+            segments.drain(..2);
+        }
+        if segments.len() > 1 && segments[0] == "element" {
+            segments[1] = "{element id}";
+        }
+        segments.join("/")
+    }
 }